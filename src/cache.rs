@@ -0,0 +1,90 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::error::Result;
+use crate::{timestamp, FullProject};
+
+#[derive(Serialize, Deserialize)]
+struct Entry<P> {
+    #[serde(rename = "fetchedAt", with = "timestamp")]
+    fetched_at: DateTime<Utc>,
+    project: P,
+}
+
+// Every server gets its own directory, so profiles for different servers
+// never see each other's projects. Anything but letters, digits and dashes
+// is written as _XX, which keeps different urls apart
+fn server_dir(server: &str) -> String {
+    let mut dir = String::with_capacity(server.len());
+    for byte in server.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' {
+            dir.push(byte as char);
+        } else {
+            dir.push_str(&format!("_{:02x}", byte));
+        }
+    }
+    dir
+}
+
+// Ids end up in file names, anything that could escape the cache
+// directory just doesn't get cached
+fn path(server: &str, project_id: &str) -> Option<PathBuf> {
+    let valid = !project_id.is_empty()
+        && project_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return None;
+    }
+
+    let dirs = ProjectDirs::from("", "", env!("CARGO_PKG_NAME"))?;
+    let file = format!("{}.json", project_id);
+    let dir = dirs.config_dir().join("cache").join(server_dir(server));
+    Some(dir.join(file))
+}
+
+// A missing, unreadable or too old entry is a miss, the cache is only ever
+// a fallback for the server. `server` is the url the api is reached at
+pub fn load(server: &str, project_id: &str, max_age: Option<Duration>) -> Option<FullProject> {
+    let path = path(server, project_id)?;
+    let content = std::fs::read_to_string(&path).ok()?;
+    let entry = match serde_json::from_str::<Entry<FullProject>>(&content) {
+        Ok(entry) => entry,
+        Err(e) => {
+            debug!("ignoring broken cache entry {}: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    let age = (Utc::now() - entry.fetched_at).to_std().unwrap_or_default();
+    if max_age.is_some_and(|max_age| age > max_age) {
+        debug!("cache entry {} is {}s old", path.display(), age.as_secs());
+        return None;
+    }
+
+    Some(entry.project)
+}
+
+pub fn store(server: &str, project: &FullProject) -> Result<()> {
+    let Some(path) = path(server, &project.id) else {
+        return Ok(());
+    };
+
+    let entry = Entry {
+        fetched_at: Utc::now(),
+        project,
+    };
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let content = serde_json::to_string(&entry).map_err(std::io::Error::from)?;
+    std::fs::write(path, content)?;
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::output::Output;
+
+#[derive(Deserialize, Default, Clone, Debug)]
+pub struct Profile {
+    pub server: Option<String>,
+    pub base_path: Option<String>,
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+pub struct Config {
+    pub server: Option<String>,
+    pub base_path: Option<String>,
+    pub token: Option<String>,
+    pub timeout: Option<u64>,
+    pub connect_timeout: Option<u64>,
+    pub default_output: Option<Output>,
+
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+impl Config {
+    // Picks the requested profile, falling back to `default_profile`
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile> {
+        let Some(name) = name.or(self.default_profile.as_deref()) else {
+            return Ok(Profile::default());
+        };
+
+        self.profiles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::Usage(format!("unknown profile '{}'", name)))
+    }
+}
+
+pub fn path() -> Option<PathBuf> {
+    let dirs = ProjectDirs::from("", "", env!("CARGO_PKG_NAME"))?;
+    Some(dirs.config_dir().join("config.toml"))
+}
+
+pub fn load() -> Result<Config> {
+    let Some(path) = path() else {
+        return Ok(Config::default());
+    };
+
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(e.into()),
+    };
+
+    toml::from_str(&content).map_err(|e| Error::Config(path, e))
+}
@@ -0,0 +1,111 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::SubCommand;
+
+// Name of the file that ties a directory to a project, like .git
+pub const FILE: &str = ".onix";
+
+// Project ids given as this are taken from the nearest .onix file
+pub const CURRENT: &str = ".";
+
+#[derive(Serialize, Deserialize)]
+struct ProjectFile {
+    project_id: String,
+}
+
+// Looks for a .onix file in `dir` and then in every parent of it
+fn find(dir: &Path) -> Result<Option<String>> {
+    for dir in dir.ancestors() {
+        let path = dir.join(FILE);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        let file = toml::from_str::<ProjectFile>(&content).map_err(|e| Error::Config(path, e))?;
+        return Ok(Some(file.project_id));
+    }
+
+    Ok(None)
+}
+
+pub fn init(project_id: &str) -> Result<PathBuf> {
+    let path = std::env::current_dir()?.join(FILE);
+    if path.exists() {
+        return Err(Error::Usage(format!("{} already exists", path.display())));
+    }
+
+    let file = ProjectFile {
+        project_id: project_id.to_string(),
+    };
+    let content = toml::to_string(&file).expect("project file should serialize");
+    std::fs::write(&path, content)?;
+
+    Ok(path)
+}
+
+// Swaps every "." project id of the command for the one of the .onix file
+pub fn resolve(command: &mut SubCommand) -> Result<()> {
+    // `new-list <NAME>` is short for `new-list . <NAME>`, clap can't have an
+    // optional argument in front of a required one so it is sorted out here
+    if let SubCommand::NewList {
+        project_id,
+        name: name @ None,
+        ..
+    } = command
+    {
+        *name = Some(std::mem::replace(project_id, CURRENT.to_string()));
+    }
+
+    let project_id = match command {
+        SubCommand::GetProject { project_id, .. }
+        | SubCommand::RenameProject { project_id, .. }
+        | SubCommand::SetProjectColor { project_id, .. }
+        | SubCommand::DeleteProject { project_id }
+        | SubCommand::NewList { project_id, .. }
+        | SubCommand::NewLists { project_id, .. }
+        | SubCommand::PruneEmptyLists { project_id }
+        | SubCommand::Stats { project_id }
+        | SubCommand::Export { project_id }
+        | SubCommand::Open { project_id, .. }
+        | SubCommand::Watch { project_id, .. }
+        | SubCommand::MoveList {
+            target_project_id: project_id,
+            ..
+        }
+        | SubCommand::SearchItems {
+            project_id: Some(project_id),
+            all_projects: false,
+            ..
+        }
+        | SubCommand::NewListItem {
+            by_name: Some(project_id),
+            ..
+        }
+        | SubCommand::CopyList {
+            target_project: Some(project_id),
+            ..
+        } => project_id,
+
+        _ => return Ok(()),
+    };
+
+    if project_id != CURRENT {
+        return Ok(());
+    }
+
+    let dir = std::env::current_dir()?;
+    *project_id = find(&dir)?.ok_or_else(|| {
+        Error::Usage(format!(
+            "no {} file in {} or any parent directory, pass a project id or run `onix init`",
+            FILE,
+            dir.display()
+        ))
+    })?;
+
+    Ok(())
+}
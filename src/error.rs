@@ -0,0 +1,203 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{request} failed: {source}")]
+    Http {
+        request: String,
+        source: reqwest::Error,
+    },
+
+    #[error("failed to decode response of {request}: {source}")]
+    Decode {
+        request: String,
+        source: serde_json::Error,
+    },
+
+    #[error("expected JSON from {request} but got {content_type}: {body}")]
+    NotJson {
+        request: String,
+        content_type: String,
+        body: String,
+    },
+
+    #[error("unexpected response shape from {request} at '{path}': {source}")]
+    Shape {
+        request: String,
+        path: String,
+        source: serde_json::Error,
+    },
+
+    #[error("failed to set up the http client: {0}")]
+    Client(reqwest::Error),
+
+    #[error("{0} not found")]
+    NotFound(String),
+
+    // The answer of `exists`, not a failure of the request
+    #[error("{0} does not exist")]
+    Missing(String),
+
+    #[error("authentication failed ({0})")]
+    Auth(StatusCode),
+
+    #[error("server returned {status}: {body}")]
+    Server {
+        status: StatusCode,
+        // Cut down by `excerpt`, error pages can be whole documents
+        body: String,
+        // From the Retry-After header of a 429 or 503
+        retry_after: Option<Duration>,
+    },
+
+    #[error("aborted")]
+    Aborted,
+
+    #[error("interrupted")]
+    Interrupted,
+
+    #[error("{failed} of {total} operations failed")]
+    Partial { failed: usize, total: usize },
+
+    #[error("{0}")]
+    Usage(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid config file {}: {1}", .0.display())]
+    Config(PathBuf, toml::de::Error),
+
+    #[error("invalid project file {} at '{path}': {source}", .file.display())]
+    ProjectFile {
+        file: PathBuf,
+        path: String,
+        source: serde_json::Error,
+    },
+
+    #[error("invalid list file {}: {1}", .0.display())]
+    ListFile(PathBuf, serde_json::Error),
+}
+
+impl Error {
+    // `request` is the method and url like "GET http://host/api/project",
+    // the url is taken out of the reqwest error so it isn't there twice
+    pub fn http(request: String, source: reqwest::Error) -> Error {
+        Error::Http {
+            request,
+            source: source.without_url(),
+        }
+    }
+
+    pub fn decode(request: String, source: serde_json::Error) -> Error {
+        Error::Decode { request, source }
+    }
+
+    // `send` only knows the path that 404'd, callers that know what they
+    // were looking for use this to say e.g. "project <id> not found"
+    pub fn named(self, kind: &str, id: &str) -> Error {
+        match self {
+            Error::NotFound(_) => Error::NotFound(format!("{} {}", kind, id)),
+            e => e,
+        }
+    }
+
+    // Exit codes are part of the interface, scripts rely on them to tell
+    // failures apart, see the README before changing any of them
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Io(e) if e.kind() == std::io::ErrorKind::InvalidInput => 2,
+            Error::Usage(_)
+            | Error::Config(..)
+            | Error::ProjectFile { .. }
+            | Error::ListFile(..) => 2,
+            Error::Http { .. } => 3,
+            Error::NotFound(_) | Error::Auth(_) => 4,
+            Error::Server { status, .. } if status.is_client_error() => 4,
+            Error::Server { status, .. } if status.is_server_error() => 5,
+            Error::Interrupted => 130,
+            _ => 1,
+        }
+    }
+}
+
+// serde reports a missing field at the object that lacks it, the field
+// itself is only in the message, like "missing field `done`"
+pub fn field_path(path: String, source: &serde_json::Error) -> String {
+    let message = source.to_string();
+    let field = message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.split_once('`'))
+        .map(|(field, _)| field);
+
+    match field {
+        Some(field) if path == "." => field.to_string(),
+        Some(field) => format!("{}.{}", path, field),
+        None => path,
+    }
+}
+
+// Error bodies longer than this are cut off, they end up in a single line
+// of the error message
+const MAX_EXCERPT: usize = 200;
+
+// Turns a response body into something that fits into an error message.
+// Markup is dropped so an HTML error page comes down to its text, and runs
+// of whitespace are folded into single spaces
+pub fn excerpt(body: &str, html: bool) -> String {
+    let mut text = String::with_capacity(body.len());
+    let mut in_tag = false;
+    for c in body.chars() {
+        match c {
+            '<' if html => in_tag = true,
+            '>' if html && in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if in_tag => {}
+            c => text.push(c),
+        }
+    }
+
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return "(empty body)".to_string();
+    }
+
+    match text.char_indices().nth(MAX_EXCERPT) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_pages_come_down_to_their_text() {
+        let body = "<!DOCTYPE html>\n<html>\n  <head><title>500 Internal Server Error</title></head>\n  <body><h1>Oops</h1></body>\n</html>";
+        assert_eq!(excerpt(body, true), "500 Internal Server Error Oops");
+    }
+
+    #[test]
+    fn plain_text_keeps_angle_brackets() {
+        assert_eq!(excerpt("expected <id>\n", false), "expected <id>");
+        assert_eq!(excerpt("  \n", false), "(empty body)");
+    }
+
+    #[test]
+    fn long_bodies_are_cut_on_a_character_boundary() {
+        let body = "ü".repeat(MAX_EXCERPT + 10);
+        let text = excerpt(&body, false);
+        assert_eq!(text, format!("{}...", "ü".repeat(MAX_EXCERPT)));
+
+        let body = "ü".repeat(MAX_EXCERPT);
+        assert_eq!(excerpt(&body, false), body);
+    }
+}
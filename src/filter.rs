@@ -0,0 +1,30 @@
+use clap::Args;
+
+use crate::ListItem;
+
+#[derive(Args, Clone, Debug)]
+pub struct ItemFilterArgs {
+    /// Only show items that are done
+    #[arg(long, conflicts_with = "todo_only")]
+    pub done_only: bool,
+
+    /// Only show items that aren't done yet
+    #[arg(long)]
+    pub todo_only: bool,
+}
+
+impl ItemFilterArgs {
+    pub fn matches(&self, item: &ListItem) -> bool {
+        if self.done_only {
+            item.done
+        } else if self.todo_only {
+            !item.done
+        } else {
+            true
+        }
+    }
+}
+
+pub fn filter_items(items: &mut Vec<ListItem>, args: &ItemFilterArgs) {
+    items.retain(|item| args.matches(item));
+}
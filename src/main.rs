@@ -1,23 +1,517 @@
-use clap::{Parser, Subcommand};
-use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use clap::{ArgAction, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use error::{Error, Result};
+use filter::ItemFilterArgs;
+use futures::{stream, StreamExt, TryStreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
+use output::{Change, ColorChoice, Output};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Method, StatusCode};
+use retry::{with_idempotency_key, with_retry, Retry};
+use serde::de::{self, DeserializeOwned, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::json;
+use sort::{ItemSortArgs, ProjectSortArgs};
+use tokio::sync::mpsc;
+use tokio_util::io::{StreamReader, SyncIoBridge};
+use tracing::{debug, trace, warn};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::prelude::*;
 
-#[derive(Parser, Debug)]
+mod cache;
+mod config;
+mod context;
+mod error;
+mod filter;
+mod output;
+mod prefix;
+mod retry;
+mod sort;
+#[cfg(test)]
+mod test_server;
+mod timestamp;
+mod tui;
+mod undo;
+
+const DEFAULT_SERVER: &str = "http://localhost:3000";
+const DEFAULT_BASE_PATH: &str = "/api";
+const DEFAULT_TIMEOUT: u64 = 30;
+// Tables written to a pipe or a file are wrapped at this width
+const DEFAULT_TABLE_WIDTH: u16 = 100;
+const DEFAULT_CONCURRENCY: usize = 8;
+
+#[derive(Parser, Clone, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(
+    after_help = "Exit codes: 0 success, 1 generic failure, 2 usage or validation error, \
+                  3 network error, 4 HTTP 4xx, 5 HTTP 5xx, 130 interrupted"
+)]
 struct Args {
+    /// Server to talk to [default: http://localhost:3000]
+    #[arg(long, env = "ONIX_SERVER", global = true)]
+    server: Option<String>,
+
+    /// Path the api is mounted under on the server, for servers behind a
+    /// reverse proxy like "/onix/api" [default: /api]
+    #[arg(long, env = "ONIX_BASE_PATH", global = true)]
+    base_path: Option<String>,
+
+    #[arg(long, env = "ONIX_TOKEN", hide_env_values = true, global = true)]
+    token: Option<String>,
+
+    /// Config profile to take the server and token from
+    #[arg(long, env = "ONIX_PROFILE", global = true)]
+    profile: Option<String>,
+
+    /// Increase logging verbosity (-v for requests, -vv for responses)
+    #[arg(
+        short,
+        long,
+        action = ArgAction::Count,
+        global = true,
+        conflicts_with = "quiet"
+    )]
+    verbose: u8,
+
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Don't ask for confirmation before deleting anything
+    #[arg(short, long, global = true)]
+    yes: bool,
+
+    /// Allow creating or renaming lists and items with an empty name
+    #[arg(long, global = true)]
+    allow_empty: bool,
+
+    /// Accept the start of a project, list or item id as long as only one
+    /// id starts with it
+    #[arg(long, global = true)]
+    prefix: bool,
+
+    /// Print the requests that would change something instead of sending
+    /// them. The preview always goes to stdout, also with --out
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Keep going when part of a bulk operation (batch, import,
+    /// new-lists, clear-completed) fails and report the failures at the end
+    #[arg(long, global = true)]
+    continue_on_error: bool,
+
+    /// Output format [default: json] [env: ONIX_OUTPUT, which also takes
+    /// "compact" for --output json --compact]
+    #[arg(short, long, value_enum, global = true)]
+    output: Option<Output>,
+
+    #[arg(long, value_enum, default_value_t, global = true)]
+    color: ColorChoice,
+
+    /// Show timestamps as relative times like "2 days ago"
+    #[arg(long, global = true)]
+    relative: bool,
+
+    /// Print the body of every response to stderr exactly as the server
+    /// sent it, before trying to decode it
+    #[arg(long, global = true)]
+    raw: bool,
+
+    /// Only include these fields of every object in json and yaml output,
+    /// like "id,name"
+    #[arg(long, value_delimiter = ',', global = true)]
+    fields: Option<Vec<String>>,
+
+    /// Print JSON on a single line instead of pretty printing it
+    #[arg(long, visible_alias = "no-pretty", global = true)]
+    compact: bool,
+
+    /// Let long table cells run past the edge of the terminal instead of
+    /// wrapping them
+    #[arg(long, global = true)]
+    no_wrap: bool,
+
+    /// Separate the fields of plain output with this instead of a tab.
+    /// Plain output always has the same fields, --fields only picks them
+    /// for json, yaml and ndjson, use --format for other ones
+    #[arg(long, global = true)]
+    delimiter: Option<String>,
+
+    /// End every field and line of plain output with a NUL byte, for
+    /// piping into `xargs -0`
+    #[arg(long, global = true, conflicts_with = "delimiter")]
+    null_delimited: bool,
+
+    /// Print each project or item with a template instead, like
+    /// "{id} {name}". Projects have id, name, color, lists, created and
+    /// updated, items have id, name, done, list, list_id and project
+    #[arg(long, global = true)]
+    format: Option<String>,
+
+    /// Write the output to a file instead of stdout, except for the
+    /// requests previewed by --dry-run
+    #[arg(long, global = true)]
+    out: Option<PathBuf>,
+
+    /// Request timeout in seconds [default: 30]
+    #[arg(long, global = true)]
+    timeout: Option<u64>,
+
+    /// Seconds to wait for the connection to be established, on top of
+    /// that the request as a whole is still bound by --timeout
+    #[arg(long, global = true)]
+    connect_timeout: Option<u64>,
+
+    /// Send every request through this proxy, without it HTTP_PROXY,
+    /// HTTPS_PROXY and NO_PROXY are used
+    #[arg(long, global = true)]
+    proxy: Option<String>,
+
+    /// Don't verify the server's TLS certificate, anyone in between can
+    /// read and change the traffic, prefer --ca-cert
+    #[arg(long, global = true)]
+    insecure: bool,
+
+    /// Also trust this PEM encoded root certificate, for servers with a
+    /// self-signed certificate
+    #[arg(long, global = true)]
+    ca_cert: Option<PathBuf>,
+
+    #[arg(long, default_value_t = 3, global = true)]
+    retries: u32,
+
+    /// Read projects from the local cache instead of the server, only
+    /// commands that just show a whole project work like this
+    #[arg(long, global = true)]
+    offline: bool,
+
+    /// Use a cached project if it was fetched at most this many seconds
+    /// ago, with --offline older copies are an error
+    #[arg(long, global = true)]
+    max_age: Option<u64>,
+
+    /// How many requests to have in flight at once when fetching several
+    /// lists
+    #[arg(
+        long,
+        default_value_t = DEFAULT_CONCURRENCY,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+        global = true
+    )]
+    concurrency: usize,
+
+    /// Base delay in milliseconds before the first retry
+    #[arg(long, default_value_t = 200, global = true)]
+    retry_base_delay: u64,
+
+    /// Wait exactly the backoff between retries instead of adding a random
+    /// part to it, for reproducible timing
+    #[arg(long, global = true)]
+    no_jitter: bool,
+
+    /// With --verbose, warn about every request that takes longer than this
+    /// many milliseconds
+    #[arg(long, default_value_t = 1000, global = true)]
+    slow_threshold_ms: u64,
+
+    /// Don't send an Idempotency-Key header with created projects, lists
+    /// and items
+    #[arg(long, global = true)]
+    no_idempotency_keys: bool,
+
+    /// Retry creating projects, lists and items too. Only safe with a server
+    /// that honours the Idempotency-Key header, others may create duplicates
+    #[arg(long, global = true, conflicts_with = "no_idempotency_keys")]
+    retry_creates: bool,
+
     #[command(subcommand)]
     command: SubCommand,
 }
 
 #[derive(Subcommand, Clone, Debug)]
 enum SubCommand {
-    GetAllProjects,
-    GetProject { project_id: String },
-    UpdateItem { item_id: String, done: String },
-    NewList { project_id: String, name: String },
-    NewListItem { list_id: String, name: String },
-    DeleteList { list_id: String },
-    DeleteListItem { item_id: String },
+    GetAllProjects {
+        /// Only show the id and name of each project
+        #[arg(long)]
+        names_only: bool,
+
+        /// Show at most this many projects (the server has no paging, so
+        /// this is applied after fetching everything)
+        #[arg(long)]
+        limit: Option<usize>,
+
+        /// Skip this many projects before showing any (applied client side)
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+
+        /// Only show projects updated since then, either a duration like
+        /// "24h" or a date like "2024-01-01"
+        #[arg(long, value_parser = timestamp::parse_since)]
+        since: Option<DateTime<Utc>>,
+
+        /// Print projects while the response is still being parsed instead
+        /// of loading all of them first, json becomes one object per line
+        #[arg(long, conflicts_with = "sort")]
+        stream: bool,
+
+        /// Also show how many items each project has in table and csv
+        /// output, this fetches every list of every project
+        #[arg(long, conflicts_with = "stream")]
+        with_item_counts: bool,
+
+        #[command(flatten)]
+        sort: ProjectSortArgs,
+    },
+    GetProject {
+        /// Defaults to the project of the nearest .onix file
+        #[arg(default_value = context::CURRENT)]
+        project_id: String,
+        /// Print all items as one flat list instead of grouped by list
+        #[arg(long)]
+        items_only: bool,
+        #[command(flatten)]
+        filter: ItemFilterArgs,
+        #[command(flatten)]
+        sort: ItemSortArgs,
+    },
+    GetList {
+        list_id: String,
+        #[command(flatten)]
+        filter: ItemFilterArgs,
+        #[command(flatten)]
+        sort: ItemSortArgs,
+    },
+    NewProject {
+        name: String,
+        // The id can't be "color", that one is taken by the global --color
+        #[arg(id = "project_color", value_name = "COLOR", value_parser = validate_color)]
+        color: String,
+    },
+    RenameProject {
+        project_id: String,
+        name: String,
+    },
+    SetProjectColor {
+        project_id: String,
+        #[arg(id = "project_color", value_name = "COLOR", value_parser = validate_color)]
+        color: String,
+    },
+    DeleteProject {
+        project_id: String,
+    },
+    GetItem {
+        list_id: String,
+        item_id: String,
+    },
+    /// Change the name and/or the done state of an item in one request
+    UpdateItem {
+        item_id: String,
+        /// Same as --done, from before there were flags for every field
+        #[arg(value_name = "DONE", conflicts_with = "done")]
+        done_value: Option<bool>,
+        #[arg(long)]
+        name: Option<String>,
+        /// Mark the item done or not done, `--done` alone means true
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        done: Option<bool>,
+    },
+    ToggleItem {
+        item_id: String,
+    },
+    RenameItem {
+        item_id: String,
+        name: String,
+    },
+    /// Move an item to a position within its list
+    ReorderItem {
+        item_id: String,
+        position: u32,
+    },
+    MoveItem {
+        item_id: String,
+        target_list_id: String,
+    },
+    #[command(override_usage = "onix new-list [OPTIONS] [PROJECT_ID] <NAME>")]
+    NewList {
+        /// Defaults to the project of the nearest .onix file, the only
+        /// argument is then the name
+        project_id: String,
+        name: Option<String>,
+        /// Print the whole created list instead of just its id
+        #[arg(long)]
+        full: bool,
+    },
+    /// Create a list for every name in a file, either one per line or as a
+    /// JSON array of strings
+    NewLists {
+        project_id: String,
+        file: PathBuf,
+    },
+    NewListItem {
+        list_id: String,
+        /// Name of the item, when omitted each line of stdin becomes an item
+        name: Option<String>,
+        /// Look LIST_ID up as the name of a list in this project instead
+        #[arg(long, value_name = "PROJECT_ID")]
+        by_name: Option<String>,
+        /// Print every created item instead of just its id
+        #[arg(long)]
+        full: bool,
+    },
+    /// Create a copy of a list with all of its items
+    CopyList {
+        list_id: String,
+        /// Name of the copy, defaults to the name of the original list
+        #[arg(long)]
+        name: Option<String>,
+        /// Project to create the copy in, defaults to the original project
+        #[arg(long)]
+        target_project: Option<String>,
+        /// Keep the done state of the items instead of resetting them
+        #[arg(long)]
+        keep_done: bool,
+    },
+    /// Move a list and all of its items to another project
+    MoveList {
+        list_id: String,
+        target_project_id: String,
+    },
+    RenameList {
+        list_id: String,
+        name: String,
+    },
+    DeleteList {
+        list_id: String,
+    },
+    DeleteListItem {
+        item_id: String,
+    },
+    ClearCompleted {
+        list_id: String,
+    },
+    /// Delete every list of a project that has no items left
+    PruneEmptyLists {
+        project_id: String,
+    },
+    #[command(
+        override_usage = "onix search-items [OPTIONS] <PROJECT_ID> <QUERY>\n       \
+                                onix search-items [OPTIONS] --all-projects <QUERY>"
+    )]
+    SearchItems {
+        #[arg(required = true)]
+        project_id: Option<String>,
+        query: Option<String>,
+        /// Search every project, the only argument is then the query
+        #[arg(long)]
+        all_projects: bool,
+        #[command(flatten)]
+        filter: ItemFilterArgs,
+        #[command(flatten)]
+        sort: ItemSortArgs,
+    },
+    Stats {
+        /// Defaults to the project of the nearest .onix file
+        #[arg(default_value = context::CURRENT)]
+        project_id: String,
+    },
+    Export {
+        /// Defaults to the project of the nearest .onix file
+        #[arg(default_value = context::CURRENT)]
+        project_id: String,
+    },
+    Import {
+        file: PathBuf,
+    },
+    /// Run the commands in a file one after the other, one per line
+    /// without the leading "onix", blank lines and lines starting with #
+    /// are skipped. $1 stands for the id created by the latest line that
+    /// created something, $2 for the one before and so on
+    Batch {
+        file: PathBuf,
+    },
+    /// Show what changed between two exported projects
+    Diff {
+        a: PathBuf,
+        b: PathBuf,
+    },
+    /// Open the project in the web UI
+    Open {
+        /// Defaults to the project of the nearest .onix file
+        #[arg(default_value = context::CURRENT)]
+        project_id: String,
+        /// Only print the URL instead of opening a browser
+        #[arg(long)]
+        print_url: bool,
+    },
+    /// Print the version of onix and of the server it talks to
+    Version,
+    /// Check that the server is reachable and print the round trip time
+    Ping,
+    /// Tie the current directory to a project by writing a .onix file
+    ///
+    /// Commands run in it or below take "." as that project. get-project,
+    /// stats, export, watch, open and new-list can leave it out entirely.
+    Init {
+        project_id: String,
+    },
+    /// Exit with 0 when the project or list exists and with 1 when not
+    #[command(group = clap::ArgGroup::new("target").required(true))]
+    Exists {
+        #[arg(long, group = "target")]
+        project: Option<String>,
+        #[arg(long, group = "target")]
+        list: Option<String>,
+    },
+    /// Browse projects, lists and items interactively
+    Tui,
+    /// Re-fetch a project every few seconds and highlight what changed
+    Watch {
+        /// Defaults to the project of the nearest .onix file
+        #[arg(default_value = context::CURRENT)]
+        project_id: String,
+        /// Seconds between refreshes
+        #[arg(long, default_value_t = 5)]
+        interval: u64,
+    },
+    Completions {
+        shell: Shell,
+    },
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Show what was recently deleted, with enough detail to recreate it
+    UndoLog {
+        /// How many of the most recent deletions to show
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+enum ConfigCommand {
+    /// Print where the config file is looked for
+    Path,
+}
+
+fn validate_color(color: &str) -> Result<String, String> {
+    if output::parse_color(color).is_some() {
+        Ok(color.to_string())
+    } else {
+        Err(format!(
+            "'{}' is not a hex color in the form #RRGGBB",
+            color
+        ))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -30,10 +524,10 @@ struct Project {
     id: String,
     name: String,
     color: String,
-    #[serde(rename = "createdAt")]
-    created_at: String,
-    #[serde(rename = "updatedAt")]
-    updated_at: String,
+    #[serde(rename = "createdAt", with = "timestamp")]
+    created_at: DateTime<Utc>,
+    #[serde(rename = "updatedAt", with = "timestamp")]
+    updated_at: DateTime<Utc>,
 
     lists: Option<Vec<Id>>,
 }
@@ -44,6 +538,7 @@ struct List {
     name: String,
     #[serde(rename = "projectId")]
     project_id: String,
+    #[serde(default)]
     items: Vec<ListItem>,
 }
 
@@ -54,6 +549,8 @@ struct ListItem {
     done: bool,
     #[serde(rename = "listId")]
     list_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    position: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -61,189 +558,2355 @@ struct FullProject {
     id: String,
     name: String,
     color: String,
-    #[serde(rename = "createdAt")]
-    created_at: String,
-    #[serde(rename = "updatedAt")]
-    updated_at: String,
+    #[serde(rename = "createdAt", with = "timestamp")]
+    created_at: DateTime<Utc>,
+    #[serde(rename = "updatedAt", with = "timestamp")]
+    updated_at: DateTime<Utc>,
 
     lists: Vec<List>,
 }
 
-async fn get_all_projects() -> Option<Vec<Project>> {
-    let result = reqwest::get("http://localhost:3000/api/project/all")
-        .await
-        .ok()?
-        .json::<Vec<Project>>()
-        .await
-        .ok()?;
-    Some(result)
+#[derive(Serialize, Debug)]
+struct FlatItem<'a> {
+    #[serde(rename = "projectName", skip_serializing_if = "Option::is_none")]
+    project_name: Option<&'a str>,
+    #[serde(rename = "listName")]
+    list_name: &'a str,
+    #[serde(flatten)]
+    item: &'a ListItem,
+}
+
+impl List {
+    fn items(&self) -> impl Iterator<Item = FlatItem<'_>> {
+        self.items.iter().map(|item| FlatItem {
+            project_name: None,
+            list_name: &self.name,
+            item,
+        })
+    }
+}
+
+impl FullProject {
+    fn items(&self) -> impl Iterator<Item = FlatItem<'_>> {
+        self.lists.iter().flat_map(move |list| {
+            list.items().map(move |item| FlatItem {
+                project_name: Some(&self.name),
+                ..item
+            })
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct ListStats {
+    list_id: String,
+    name: String,
+    done: usize,
+    total: usize,
+    percent: usize,
+}
+
+impl ListStats {
+    fn new(list_id: &str, name: &str, done: usize, total: usize) -> Self {
+        let percent = (done * 100).checked_div(total).unwrap_or(0);
+
+        Self {
+            list_id: list_id.to_string(),
+            name: name.to_string(),
+            done,
+            total,
+            percent,
+        }
+    }
 }
 
-async fn get_project(id: &str) -> Option<Project> {
-    // TODO(patrik): Handle errors
-    let url = format!("http://localhost:3000/api/project?id={}", id);
-    let result = reqwest::get(url).await.ok()?.json::<Project>().await.ok()?;
-    Some(result)
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+enum DiffKind {
+    Added,
+    Removed,
+    Changed,
 }
 
-async fn get_project_list(list_id: &str) -> Option<List> {
-    let url = format!("http://localhost:3000/api/project/list?id={}", list_id);
-    let result = reqwest::get(url).await.ok()?.json::<List>().await.ok()?;
-    Some(result)
+// One line of `diff`, the old values are only set for what changed
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DiffEntry<'a> {
+    change: DiffKind,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    id: &'a str,
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    done: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_done: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    list_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_list_id: Option<&'a str>,
 }
 
-async fn get_full_project(project_id: &str) -> Option<FullProject> {
-    let project = get_project(project_id).await?;
-    let mut lists = Vec::new();
-    for list in project.lists.as_ref()?.iter() {
-        let list = get_project_list(&list.id).await?;
-        lists.push(list);
+impl<'a> DiffEntry<'a> {
+    fn new(change: DiffKind, kind: &'static str, id: &'a str, name: &'a str) -> Self {
+        Self {
+            change,
+            kind,
+            id,
+            name,
+            old_name: None,
+            done: None,
+            old_done: None,
+            list_id: None,
+            old_list_id: None,
+        }
     }
+}
 
-    Some(FullProject {
-        id: project.id,
-        name: project.name,
-        color: project.color,
-        created_at: project.created_at,
-        updated_at: project.updated_at,
+// Lists and items are matched up by id, so something that was recreated
+// (like after an import) shows up as removed and added again
+fn diff_projects<'a>(a: &'a FullProject, b: &'a FullProject) -> Vec<DiffEntry<'a>> {
+    let mut entries = Vec::new();
 
-        lists,
+    let old_lists = a
+        .lists
+        .iter()
+        .map(|l| (l.id.as_str(), l))
+        .collect::<HashMap<_, _>>();
+    let new_lists = b
+        .lists
+        .iter()
+        .map(|l| (l.id.as_str(), l))
+        .collect::<HashMap<_, _>>();
+    for list in &a.lists {
+        if !new_lists.contains_key(list.id.as_str()) {
+            entries.push(DiffEntry::new(
+                DiffKind::Removed,
+                "list",
+                &list.id,
+                &list.name,
+            ));
+        }
+    }
+    for list in &b.lists {
+        match old_lists.get(list.id.as_str()) {
+            None => entries.push(DiffEntry::new(
+                DiffKind::Added,
+                "list",
+                &list.id,
+                &list.name,
+            )),
+            Some(old) if old.name != list.name => entries.push(DiffEntry {
+                old_name: Some(&old.name),
+                ..DiffEntry::new(DiffKind::Changed, "list", &list.id, &list.name)
+            }),
+            Some(_) => {}
+        }
+    }
+
+    let items = |project: &'a FullProject| {
+        project
+            .lists
+            .iter()
+            .flat_map(|l| &l.items)
+            .map(|i| (i.id.as_str(), i))
+            .collect::<HashMap<_, _>>()
+    };
+    let old_items = items(a);
+    let new_items = items(b);
+    for item in a.lists.iter().flat_map(|l| &l.items) {
+        if !new_items.contains_key(item.id.as_str()) {
+            entries.push(DiffEntry::new(
+                DiffKind::Removed,
+                "item",
+                &item.id,
+                &item.name,
+            ));
+        }
+    }
+    for item in b.lists.iter().flat_map(|l| &l.items) {
+        let Some(old) = old_items.get(item.id.as_str()) else {
+            entries.push(DiffEntry::new(
+                DiffKind::Added,
+                "item",
+                &item.id,
+                &item.name,
+            ));
+            continue;
+        };
+
+        let mut entry = DiffEntry::new(DiffKind::Changed, "item", &item.id, &item.name);
+        if old.name != item.name {
+            entry.old_name = Some(&old.name);
+        }
+        if old.done != item.done {
+            entry.done = Some(item.done);
+            entry.old_done = Some(old.done);
+        }
+        if old.list_id != item.list_id {
+            entry.list_id = Some(&item.list_id);
+            entry.old_list_id = Some(&old.list_id);
+        }
+
+        if entry.old_name.is_some() || entry.old_done.is_some() || entry.old_list_id.is_some() {
+            entries.push(entry);
+        }
+    }
+
+    entries
+}
+
+fn read_project_file(file: PathBuf) -> Result<FullProject> {
+    let content = std::fs::read_to_string(&file)?;
+    // Checked as a whole before anything is created, so a broken file
+    // can't leave a half imported project behind
+    let de = &mut serde_json::Deserializer::from_str(&content);
+    serde_path_to_error::deserialize(de).map_err(|e| {
+        let path = e.path().to_string();
+        let source = e.into_inner();
+        Error::ProjectFile {
+            file,
+            path: error::field_path(path, &source),
+            source,
+        }
     })
 }
 
-async fn update_item(item_id: &str, done: bool) -> bool {
-    let url = "http://localhost:3000/api/project/list/item";
-    let client = reqwest::Client::new();
-    let value = json!({
-        "id": item_id,
-        "data": {
-            "done": done,
+struct Api {
+    base: String,
+    // Either empty or starting with a slash, without one at the end
+    base_path: String,
+    client: reqwest::Client,
+    retry: Retry,
+    dry_run: bool,
+    progress: bool,
+    idempotency_keys: bool,
+    retry_creates: bool,
+    raw: bool,
+    concurrency: usize,
+    offline: bool,
+    max_age: Option<Duration>,
+    metrics: Metrics,
+    // Only set with --verbose
+    slow_threshold: Option<Duration>,
+}
+
+// Counted across every request the command makes, the requests can
+// overlap so `busy` may well be more than the wall time
+#[derive(Debug)]
+struct Metrics {
+    started: Instant,
+    requests: AtomicUsize,
+    busy: Mutex<Duration>,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            requests: AtomicUsize::new(0),
+            busy: Mutex::new(Duration::ZERO),
         }
-    });
-    let res = client.patch(url).json(&value).send().await;
+    }
 
-    if let Ok(res) = res {
-        res.status().is_success()
-    } else {
-        false
+    fn record(&self, elapsed: Duration) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        *self.busy.lock().unwrap() += elapsed;
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "{} requests taking {} ms combined, {} ms wall time",
+            self.requests.load(Ordering::Relaxed),
+            self.busy.lock().unwrap().as_millis(),
+            self.started.elapsed().as_millis()
+        )
     }
 }
 
-async fn new_list(project_id: &str, name: &str) -> Option<String> {
-    let url = "http://localhost:3000/api/project/list";
-    let client = reqwest::Client::new();
-    let data = json!({
-        "name": name,
-        "projectId": project_id,
-    });
-    let res = client.post(url).json(&data).send().await;
+// Both "onix/api" and "/onix/api/" end up as "/onix/api", a server
+// mounted at the root has an empty one
+fn normalize_base_path(path: &str) -> String {
+    match path.trim_matches('/') {
+        "" => String::new(),
+        path => format!("/{}", path),
+    }
+}
 
-    if let Ok(res) = res {
-        if res.status().is_success() {
-            return Some(res.text().await.unwrap());
+impl Api {
+    // Everything but where to find the server starts out at the defaults of
+    // the flags
+    fn new(server: &str, base_path: &str, client: reqwest::Client) -> Self {
+        Self {
+            base: server.trim_end_matches('/').to_string(),
+            base_path: normalize_base_path(base_path),
+            client,
+            retry: Retry {
+                retries: 0,
+                base_delay: Duration::ZERO,
+                jitter: true,
+            },
+            dry_run: false,
+            progress: false,
+            idempotency_keys: true,
+            retry_creates: false,
+            raw: false,
+            concurrency: DEFAULT_CONCURRENCY,
+            offline: false,
+            max_age: None,
+            metrics: Metrics::new(),
+            slow_threshold: None,
         }
+    }
 
-        None
-    } else {
-        None
+    fn url(&self, path: &str) -> String {
+        format!("{}{}{}", self.base, self.base_path, path)
     }
-}
 
-async fn new_list_item(list_id: &str, name: &str) -> Option<String> {
-    let url = "http://localhost:3000/api/project/list/item";
-    let client = reqwest::Client::new();
-    let data = json!({
-        "name": name,
-        "listId": list_id,
-    });
-    let res = client.post(url).json(&data).send().await;
+    // The web UI is served next to the api, at the mount point without the
+    // /api at the end
+    fn web_url(&self, path: &str) -> String {
+        let mount = self
+            .base_path
+            .strip_suffix("/api")
+            .unwrap_or(&self.base_path);
+        format!("{}{}{}", self.base, mount, path)
+    }
 
-    if let Ok(res) = res {
-        if res.status().is_success() {
-            return Some(res.text().await.unwrap());
+    // Hidden bars swallow everything, so callers can use them unconditionally
+    fn progress_bar(&self, len: usize) -> ProgressBar {
+        if !self.progress {
+            return ProgressBar::hidden();
         }
 
-        None
-    } else {
-        None
+        let style = ProgressStyle::with_template("{bar:40} {pos}/{len} {msg}")
+            .expect("progress template should be valid");
+        ProgressBar::new(len as u64).with_style(style)
     }
 }
 
-async fn delete_list(list_id: &str) -> bool {
-    // TODO(patrik): Url encode the list id
-    let url = format!("http://localhost:3000/api/project/list?id={}", list_id);
-    let client = reqwest::Client::new();
-    let res = client.delete(url).send().await;
+// Printed straight to stdout, `send` has no access to --out. The preview
+// is for reading along and not output to keep
+fn print_dry_run(req: &reqwest::Request) {
+    println!("{} {}", req.method(), req.url());
 
-    if let Ok(res) = res {
-        res.status().is_success()
-    } else {
-        false
+    let Some(body) = req.body().and_then(|body| body.as_bytes()) else {
+        return;
+    };
+
+    match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(value) => println!("{}", serde_json::to_string_pretty(&value).unwrap()),
+        Err(_) => println!("{}", String::from_utf8_lossy(body)),
     }
 }
 
-async fn delete_list_item(item_id: &str) -> bool {
-    // TODO(patrik): Url encode the list id
-    let url = format!("http://localhost:3000/api/project/list/item?id={}", item_id);
-    let client = reqwest::Client::new();
-    let res = client.delete(url).send().await;
+async fn send(api: &Api, req: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+    let (client, req) = req.build_split();
+    let req = req.map_err(|e| Error::Usage(format!("invalid request: {}", e)))?;
+    let request = format!("{} {}", req.method(), req.url());
+    debug!("{}", request);
 
-    if let Ok(res) = res {
-        res.status().is_success()
-    } else {
-        false
+    if api.offline {
+        return Err(Error::Usage(format!(
+            "can't {} {} while offline",
+            req.method(),
+            req.url()
+        )));
+    }
+
+    // Only the mutating requests are skipped so that commands can still
+    // look things up while previewing what they would change
+    if api.dry_run && req.method() != Method::GET && req.method() != Method::HEAD {
+        print_dry_run(&req);
+        return Ok(http::Response::new(Vec::new()).into());
     }
+
+    let start = Instant::now();
+    let res = client.execute(req).await;
+    let elapsed = start.elapsed();
+    api.metrics.record(elapsed);
+    if api
+        .slow_threshold
+        .is_some_and(|threshold| elapsed > threshold)
+    {
+        warn!("slow request: {} took {}ms", request, elapsed.as_millis());
+    }
+    let res = res.map_err(|e| Error::http(request, e))?;
+
+    let status = res.status();
+    trace!("{} {}", status, res.url());
+    if status.is_success() {
+        return Ok(res);
+    }
+
+    let path = res.url().path().to_string();
+    let retry_after = res
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(retry::parse_retry_after);
+    let html = is_html(&res);
+    let body = res.text().await.unwrap_or_default();
+    // Error bodies are cut down below, --raw is there to see all of them
+    if api.raw {
+        eprintln!("{}", body);
+    }
+
+    if status == StatusCode::NOT_FOUND {
+        return Err(Error::NotFound(path));
+    }
+
+    if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+        return Err(Error::Auth(status));
+    }
+
+    Err(Error::Server {
+        status,
+        body: error::excerpt(&body, html),
+        retry_after,
+    })
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+fn content_type(res: &reqwest::Response) -> Option<&str> {
+    let value = res.headers().get(CONTENT_TYPE)?.to_str().ok()?;
+    value.split(';').next().map(str::trim)
+}
 
-    match args.command {
-        SubCommand::GetAllProjects => {
-            let projects = get_all_projects().await.unwrap();
-            print!("{}", serde_json::to_string_pretty(&projects).unwrap())
+fn is_html(res: &reqwest::Response) -> bool {
+    content_type(res).is_some_and(|t| t.eq_ignore_ascii_case("text/html"))
+}
+
+// Only GET responses have a body worth decoding
+async fn decode<T: DeserializeOwned>(api: &Api, res: reqwest::Response) -> Result<T> {
+    let request = format!("GET {}", res.url());
+    let content = content_type(&res).map(str::to_string);
+    let html = is_html(&res);
+    let body = res
+        .bytes()
+        .await
+        .map_err(|e| Error::http(request.clone(), e))?;
+    if api.raw {
+        eprintln!("{}", String::from_utf8_lossy(&body));
+    }
+
+    // Going through serde_path_to_error so that a renamed or missing field
+    // says where it was expected instead of just a line and column
+    let de = &mut serde_json::Deserializer::from_slice(&body);
+    serde_path_to_error::deserialize(de).map_err(|e| {
+        let path = e.path().to_string();
+        let source = e.into_inner();
+        let path = error::field_path(path, &source);
+        // Proxies and login pages answer with a page instead of JSON, and
+        // what the page says is more use than where the parser gave up
+        let not_json = content.as_deref().filter(|t| !t.contains("json"));
+        if let (Some(content_type), false) = (not_json, source.is_data()) {
+            Error::NotJson {
+                request,
+                content_type: content_type.to_string(),
+                body: error::excerpt(&String::from_utf8_lossy(&body), html),
+            }
+        } else if source.is_data() {
+            Error::Shape {
+                request,
+                path,
+                source,
+            }
+        } else {
+            Error::decode(request, source)
         }
+    })
+}
+
+// Creating something answers with just the id of it
+async fn created_id(res: reqwest::Response) -> Result<String> {
+    let request = format!("POST {}", res.url());
+    res.text().await.map_err(|e| Error::http(request, e))
+}
+
+#[derive(Deserialize, Debug)]
+struct ServerVersion {
+    version: String,
+    build: Option<String>,
+}
+
+// Older servers don't have the endpoint at all, that's not an error
+async fn get_server_version(api: &Api) -> Result<Option<ServerVersion>> {
+    match with_retry(api, api.client.get(api.url("/version"))).await {
+        Ok(res) => decode(api, res).await.map(Some),
+        Err(Error::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
 
-        SubCommand::GetProject { project_id } => {
-            let project = get_full_project(&project_id).await.unwrap();
-            print!("{}", serde_json::to_string_pretty(&project).unwrap());
+// Semver rules, before 1.0 the minor version is the one that breaks things
+fn compatible_versions(a: &str, b: &str) -> bool {
+    let a = a.trim_start_matches('v').split('.').collect::<Vec<_>>();
+    let b = b.trim_start_matches('v').split('.').collect::<Vec<_>>();
+
+    match (a.first(), b.first()) {
+        (Some(major), Some(other)) if major != other => false,
+        (Some(&"0"), _) => a.get(1) == b.get(1),
+        _ => true,
+    }
+}
+
+// Servers that don't allow HEAD get a GET instead, for requests that only
+// care whether there was an answer
+async fn head_or_get(api: &Api, url: &str, query: &[(&str, &str)]) -> Result<reqwest::Response> {
+    match with_retry(api, api.client.head(url).query(query)).await {
+        Err(Error::Server { status, .. }) if status == StatusCode::METHOD_NOT_ALLOWED => {
+            with_retry(api, api.client.get(url).query(query)).await
         }
+        res => res,
+    }
+}
 
-        SubCommand::UpdateItem { item_id, done } => {
-            if !update_item(&item_id, done == "true").await {
-                panic!("Failed to update item");
-            }
+async fn ping(api: &Api) -> Result<Duration> {
+    let start = Instant::now();
+    head_or_get(api, &api.url("/project/all"), &[]).await?;
+    Ok(start.elapsed())
+}
+
+// A HEAD request is enough to tell whether something exists
+async fn exists(api: &Api, path: &str, id: &str) -> Result<bool> {
+    match head_or_get(api, &api.url(path), &[("id", id)]).await {
+        Ok(_) => Ok(true),
+        Err(Error::NotFound(_)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+async fn project_exists(api: &Api, project_id: &str) -> Result<bool> {
+    exists(api, "/project", project_id).await
+}
+
+async fn list_exists(api: &Api, list_id: &str) -> Result<bool> {
+    exists(api, "/project/list", list_id).await
+}
+
+async fn get_all_projects(api: &Api) -> Result<Vec<Project>> {
+    let res = with_retry(api, api.client.get(api.url("/project/all"))).await?;
+    decode::<Vec<Project>>(api, res).await
+}
+
+// Hands every project of the array over to `tx` as soon as it is parsed,
+// the parser needs a blocking reader so it runs on its own thread
+async fn stream_all_projects(api: &Api, tx: mpsc::Sender<Project>) -> Result<()> {
+    struct Projects(mpsc::Sender<Project>);
+
+    impl<'de> Visitor<'de> for Projects {
+        type Value = ();
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("an array of projects")
         }
 
-        SubCommand::NewList { project_id, name } => {
-            if let Some(res) = new_list(&project_id, &name).await {
-                println!("{}", res)
-            } else {
-                panic!("Failed to create list");
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+            while let Some(project) = seq.next_element::<Project>()? {
+                self.0
+                    .blocking_send(project)
+                    .map_err(|_| de::Error::custom("stopped reading projects"))?;
             }
+            Ok(())
         }
+    }
 
-        SubCommand::NewListItem { list_id, name } => {
-            if let Some(res) = new_list_item(&list_id, &name).await {
-                println!("{}", res)
-            } else {
-                panic!("Failed to create list item");
+    let res = with_retry(api, api.client.get(api.url("/project/all"))).await?;
+    let request = format!("GET {}", res.url());
+    // --raw copies the body to stderr piece by piece as it comes in
+    let raw = api.raw;
+    let body = res
+        .bytes_stream()
+        .inspect_ok(move |chunk| {
+            if raw {
+                let _ = std::io::stderr().write_all(chunk);
             }
+        })
+        .map_err(std::io::Error::other);
+    let reader = BufReader::new(SyncIoBridge::new(StreamReader::new(body)));
+
+    let result = tokio::task::spawn_blocking(move || {
+        let mut de = serde_json::Deserializer::from_reader(reader);
+        de.deserialize_seq(Projects(tx))
+    })
+    .await
+    .expect("project parser panicked");
+    if raw {
+        eprintln!();
+    }
+    result.map_err(|e| Error::decode(request, e))
+}
+
+async fn get_project(api: &Api, id: &str) -> Result<Project> {
+    let url = api.url("/project");
+    let res = with_retry(api, api.client.get(url).query(&[("id", id)]))
+        .await
+        .map_err(|e| e.named("project", id))?;
+    decode::<Project>(api, res).await
+}
+
+async fn rename_project(api: &Api, project_id: &str, name: &str) -> Result<()> {
+    let url = api.url("/project");
+    let value = json!({
+        "id": project_id,
+        "data": {
+            "name": name,
         }
+    });
+    send(api, api.client.patch(url).json(&value))
+        .await
+        .map_err(|e| e.named("project", project_id))?;
+    Ok(())
+}
 
-        SubCommand::DeleteList { list_id } => {
-            if !delete_list(&list_id).await {
-                panic!("Failed to delete list");
-            }
+async fn set_project_color(api: &Api, project_id: &str, color: &str) -> Result<()> {
+    let url = api.url("/project");
+    let value = json!({
+        "id": project_id,
+        "data": {
+            "color": color,
         }
+    });
+    send(api, api.client.patch(url).json(&value))
+        .await
+        .map_err(|e| e.named("project", project_id))?;
+    Ok(())
+}
 
-        SubCommand::DeleteListItem { item_id } => {
-            if !delete_list_item(&item_id).await {
-                panic!("Failed to delete item");
+async fn delete_project(api: &Api, project_id: &str) -> Result<()> {
+    let url = api.url("/project");
+    with_retry(api, api.client.delete(url).query(&[("id", project_id)]))
+        .await
+        .map_err(|e| e.named("project", project_id))?;
+    Ok(())
+}
+
+async fn get_project_list(api: &Api, list_id: &str) -> Result<List> {
+    let url = api.url("/project/list");
+    let res = with_retry(api, api.client.get(url).query(&[("id", list_id)]))
+        .await
+        .map_err(|e| e.named("list", list_id))?;
+    let mut list = decode::<List>(api, res).await?;
+
+    // Items without a position keep the order the server gave them in
+    list.items
+        .sort_by_key(|item| item.position.unwrap_or(u32::MAX));
+
+    Ok(list)
+}
+
+// Like get_full_project but takes a cached copy with --offline and
+// --max-age. Only for commands that just show the project, anything that
+// goes on to change something has to see what is on the server right now
+async fn get_cached_full_project(api: &Api, project_id: &str) -> Result<FullProject> {
+    if api.offline || api.max_age.is_some() {
+        match cache::load(&api.url(""), project_id, api.max_age) {
+            Some(project) => return Ok(project),
+            None if api.offline => {
+                return Err(Error::Usage(format!(
+                    "no usable cached copy of project {}",
+                    project_id
+                )))
             }
+            None => {}
         }
     }
 
-    Ok(())
+    get_full_project(api, project_id).await
+}
+
+async fn get_full_project(api: &Api, project_id: &str) -> Result<FullProject> {
+    let project = get_project(api, project_id).await?;
+    // Projects without lists come back without the field or with null
+    let ids = project.lists.as_deref().unwrap_or_default();
+    let bar = api.progress_bar(ids.len()).with_message("lists");
+    let lists = stream::iter(ids)
+        .map(|list| get_project_list(api, &list.id))
+        .buffered(api.concurrency)
+        .inspect(|_| bar.inc(1))
+        .try_collect::<Vec<_>>()
+        .await?;
+    bar.finish_and_clear();
+
+    let project = FullProject {
+        id: project.id,
+        name: project.name,
+        color: project.color,
+        created_at: project.created_at,
+        updated_at: project.updated_at,
+
+        lists,
+    };
+
+    // Not being able to cache shouldn't stop anyone from seeing the project
+    if let Err(e) = cache::store(&api.url(""), &project) {
+        debug!("failed to cache project {}: {}", project.id, e);
+    }
+
+    Ok(project)
+}
+
+// Every project with all of its lists, fetched as one stream so the number
+// of requests in flight stays at the concurrency limit however many projects
+// there are
+async fn get_all_full_projects(api: &Api) -> Result<Vec<FullProject>> {
+    let projects = get_all_projects(api).await?;
+    let ids = projects
+        .iter()
+        .enumerate()
+        .flat_map(|(index, project)| {
+            let ids = project.lists.as_deref().unwrap_or_default();
+            ids.iter().map(move |list| (index, list))
+        })
+        .collect::<Vec<_>>();
+
+    let bar = api.progress_bar(ids.len()).with_message("lists");
+    let lists = stream::iter(ids)
+        .map(|(index, list)| async move {
+            let list = get_project_list(api, &list.id).await?;
+            Ok::<_, Error>((index, list))
+        })
+        .buffered(api.concurrency)
+        .inspect(|_| bar.inc(1))
+        .try_collect::<Vec<_>>()
+        .await?;
+    bar.finish_and_clear();
+
+    let mut full = projects
+        .into_iter()
+        .map(|project| FullProject {
+            id: project.id,
+            name: project.name,
+            color: project.color,
+            created_at: project.created_at,
+            updated_at: project.updated_at,
+
+            lists: Vec::new(),
+        })
+        .collect::<Vec<_>>();
+    for (index, list) in lists {
+        full[index].lists.push(list);
+    }
+    Ok(full)
+}
+
+// Names aren't unique, so more than one list with the name is an error
+// rather than a guess
+async fn list_id_by_name(api: &Api, project_id: &str, name: &str) -> Result<String> {
+    let project = get_full_project(api, project_id).await?;
+    let ids = project
+        .lists
+        .iter()
+        .filter(|list| list.name == name)
+        .map(|list| list.id.as_str())
+        .collect::<Vec<_>>();
+
+    match ids.as_slice() {
+        [] => Err(Error::NotFound(format!(
+            "list '{}' in project {}",
+            name, project_id
+        ))),
+        [id] => Ok(id.to_string()),
+        ids => Err(Error::Usage(format!(
+            "project {} has several lists named '{}': {}",
+            project_id,
+            name,
+            ids.join(", ")
+        ))),
+    }
+}
+
+// Fetches every list of every project at once, so the progress bar covers
+// all of them instead of one project at a time
+async fn count_items(api: &Api, projects: &[Project]) -> Result<Vec<usize>> {
+    let lists = projects
+        .iter()
+        .enumerate()
+        .flat_map(|(index, project)| {
+            let ids = project.lists.as_deref().unwrap_or_default();
+            ids.iter().map(move |list| (index, list))
+        })
+        .collect::<Vec<_>>();
+
+    let bar = api.progress_bar(lists.len()).with_message("lists");
+    let sizes = stream::iter(lists)
+        .map(|(index, list)| async move {
+            let list = get_project_list(api, &list.id).await?;
+            Ok::<_, Error>((index, list.items.len()))
+        })
+        .buffered(api.concurrency)
+        .inspect(|_| bar.inc(1))
+        .try_collect::<Vec<_>>()
+        .await?;
+    bar.finish_and_clear();
+
+    let mut counts = vec![0; projects.len()];
+    for (index, size) in sizes {
+        counts[index] += size;
+    }
+    Ok(counts)
+}
+
+async fn get_item(api: &Api, item_id: &str) -> Result<ListItem> {
+    let url = api.url("/project/list/item");
+    let res = with_retry(api, api.client.get(url).query(&[("id", item_id)]))
+        .await
+        .map_err(|e| e.named("item", item_id))?;
+    decode::<ListItem>(api, res).await
+}
+
+// Every list of every project, there is no endpoint that has them all
+async fn get_all_lists(api: &Api) -> Result<Vec<List>> {
+    let projects = get_all_projects(api).await?;
+    let ids = projects
+        .iter()
+        .flat_map(|project| project.lists.as_deref().unwrap_or_default())
+        .collect::<Vec<_>>();
+
+    let bar = api.progress_bar(ids.len()).with_message("lists");
+    let lists = stream::iter(ids)
+        .map(|list| get_project_list(api, &list.id))
+        .buffered(api.concurrency)
+        .inspect(|_| bar.inc(1))
+        .try_collect::<Vec<_>>()
+        .await?;
+    bar.finish_and_clear();
+
+    Ok(lists)
+}
+
+// Not every server has the item endpoint, a 404 from it falls back to
+// looking through the list of the item, or through every list when that
+// isn't known
+async fn find_item(api: &Api, item_id: &str, list_id: Option<&str>) -> Result<ListItem> {
+    match get_item(api, item_id).await {
+        Err(Error::NotFound(_)) => {}
+        res => return res,
+    }
+
+    let lists = match list_id {
+        Some(list_id) => vec![get_project_list(api, list_id).await?],
+        None => get_all_lists(api).await?,
+    };
+    lists
+        .into_iter()
+        .flat_map(|list| list.items)
+        .find(|item| item.id == item_id)
+        .ok_or_else(|| Error::NotFound(format!("item {}", item_id)))
+}
+
+async fn update_item(api: &Api, item_id: &str, patch: serde_json::Value) -> Result<()> {
+    let url = api.url("/project/list/item");
+    let value = json!({
+        "id": item_id,
+        "data": patch,
+    });
+    send(api, api.client.patch(url).json(&value))
+        .await
+        .map_err(|e| e.named("item", item_id))?;
+    Ok(())
+}
+
+async fn reorder_item(api: &Api, item_id: &str, position: u32) -> Result<()> {
+    let unsupported = || Error::Usage("the server doesn't support ordering items".to_string());
+
+    update_item(api, item_id, json!({ "position": position }))
+        .await
+        .map_err(|e| match e {
+            Error::Server { status, .. }
+                if status == StatusCode::BAD_REQUEST
+                    || status == StatusCode::UNPROCESSABLE_ENTITY =>
+            {
+                unsupported()
+            }
+            e => e,
+        })?;
+
+    // Older servers accept the PATCH but silently drop fields they don't
+    // know about, so check that it actually stuck
+    if !api.dry_run && find_item(api, item_id, None).await?.position != Some(position) {
+        return Err(unsupported());
+    }
+
+    Ok(())
+}
+
+async fn new_project(api: &Api, name: &str, color: &str) -> Result<String> {
+    let url = api.url("/project");
+    let data = json!({
+        "name": name,
+        "color": color,
+    });
+    let res = with_idempotency_key(api, api.client.post(url).json(&data)).await?;
+    created_id(res).await
+}
+
+async fn new_list(api: &Api, project_id: &str, name: &str) -> Result<String> {
+    let url = api.url("/project/list");
+    let data = json!({
+        "name": name,
+        "projectId": project_id,
+    });
+    let res = with_idempotency_key(api, api.client.post(url).json(&data)).await?;
+    created_id(res).await
+}
+
+async fn new_list_item(api: &Api, list_id: &str, name: &str) -> Result<String> {
+    let url = api.url("/project/list/item");
+    let data = json!({
+        "name": name,
+        "listId": list_id,
+    });
+    let res = with_idempotency_key(api, api.client.post(url).json(&data)).await?;
+    created_id(res).await
+}
+
+async fn update_list(api: &Api, list_id: &str, patch: serde_json::Value) -> Result<()> {
+    let url = api.url("/project/list");
+    let value = json!({
+        "id": list_id,
+        "data": patch,
+    });
+    send(api, api.client.patch(url).json(&value))
+        .await
+        .map_err(|e| e.named("list", list_id))?;
+    Ok(())
+}
+
+async fn rename_list(api: &Api, list_id: &str, name: &str) -> Result<()> {
+    update_list(api, list_id, json!({ "name": name })).await
+}
+
+async fn delete_list(api: &Api, list_id: &str) -> Result<()> {
+    let url = api.url("/project/list");
+    with_retry(api, api.client.delete(url).query(&[("id", list_id)]))
+        .await
+        .map_err(|e| e.named("list", list_id))?;
+    Ok(())
+}
+
+async fn delete_list_item(api: &Api, item_id: &str) -> Result<()> {
+    let url = api.url("/project/list/item");
+    with_retry(api, api.client.delete(url).query(&[("id", item_id)]))
+        .await
+        .map_err(|e| e.named("item", item_id))?;
+    Ok(())
+}
+
+#[derive(Default, Debug)]
+struct ImportProgress {
+    project_id: Option<String>,
+    lists: usize,
+    items: usize,
+    // What couldn't be imported with --continue-on-error, like "list <id>"
+    failed: Vec<(String, Error)>,
+}
+
+async fn import_item(api: &Api, list_id: &str, item: &ListItem) -> Result<String> {
+    let item_id = new_list_item(api, list_id, &item.name).await?;
+    if item.done {
+        update_item(api, &item_id, json!({ "done": true })).await?;
+    }
+
+    Ok(item_id)
+}
+
+async fn import_project(
+    api: &Api,
+    project: &FullProject,
+    progress: &mut ImportProgress,
+    verbose: bool,
+    continue_on_error: bool,
+) -> Result<String> {
+    let total = project.lists.len() + project.lists.iter().map(|l| l.items.len()).sum::<usize>();
+    let bar = api.progress_bar(total);
+
+    let project_id = new_project(api, &project.name, &project.color).await?;
+    progress.project_id = Some(project_id.clone());
+    // The old -> new ids go to stderr with the other progress reports, the
+    // output itself is just the id of the new project
+    if verbose {
+        bar.suspend(|| eprintln!("project {} -> {}", project.id, project_id));
+    }
+
+    for list in &project.lists {
+        let list_id = match new_list(api, &project_id, &list.name).await {
+            Ok(list_id) => list_id,
+            // The items have nowhere to go without their list
+            Err(e) if continue_on_error => {
+                progress.failed.push((format!("list {}", list.id), e));
+                bar.inc(1 + list.items.len() as u64);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        progress.lists += 1;
+        bar.inc(1);
+        if verbose {
+            bar.suspend(|| eprintln!("list {} -> {}", list.id, list_id));
+        }
+
+        for item in &list.items {
+            match import_item(api, &list_id, item).await {
+                Ok(item_id) => {
+                    progress.items += 1;
+                    if verbose {
+                        bar.suspend(|| eprintln!("item {} -> {}", item.id, item_id));
+                    }
+                }
+                Err(e) if continue_on_error => {
+                    progress.failed.push((format!("item {}", item.id), e));
+                }
+                Err(e) => return Err(e),
+            }
+            bar.inc(1);
+        }
+    }
+
+    bar.finish_and_clear();
+
+    Ok(project_id)
+}
+
+// Keeps refreshing until it fails, the caller is expected to race this
+// against Ctrl-C
+async fn watch_project(
+    api: &Api,
+    opts: &mut output::Options,
+    project_id: &str,
+    interval: Duration,
+) -> Result<()> {
+    let mut previous: Option<HashMap<String, bool>> = None;
+    loop {
+        let project = get_full_project(api, project_id).await?;
+
+        let mut changes = HashMap::new();
+        if let Some(previous) = &previous {
+            for item in project.lists.iter().flat_map(|l| &l.items) {
+                match previous.get(&item.id) {
+                    None => {
+                        changes.insert(item.id.as_str(), Change::Added);
+                    }
+                    Some(false) if item.done => {
+                        changes.insert(item.id.as_str(), Change::Completed);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        write!(opts.out, "\x1b[2J\x1b[H")?;
+        output::print_watch(opts, &project, &changes)?;
+        opts.out.flush()?;
+
+        previous = Some(
+            project
+                .lists
+                .iter()
+                .flat_map(|l| &l.items)
+                .map(|i| (i.id.clone(), i.done))
+                .collect(),
+        );
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+fn confirm(prompt: &str) -> Result<()> {
+    if !std::io::stdin().is_terminal() {
+        return Err(Error::Usage(
+            "refusing to delete without confirmation, pass --yes to skip the prompt".to_string(),
+        ));
+    }
+
+    eprint!("{} [y/N] ", prompt);
+    std::io::stderr().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    if matches!(answer.trim(), "y" | "Y" | "yes") {
+        Ok(())
+    } else {
+        Err(Error::Aborted)
+    }
+}
+
+// The server doesn't validate colors, so bad ones only show up as
+// uncolored output unless we point them out
+fn warn_malformed_color(verbose: u8, project_id: &str, color: &str) {
+    if verbose > 0 && output::parse_color(color).is_none() {
+        eprintln!(
+            "warning: project {} has a malformed color '{}'",
+            project_id, color
+        );
+    }
+}
+
+// Deleting still succeeded, so a log that can't be written is only worth
+// a warning
+fn log_deletion(api: &Api, entry: undo::Entry) {
+    if api.dry_run {
+        return;
+    }
+
+    if let Err(e) = undo::record(&entry) {
+        eprintln!(
+            "warning: failed to record the deletion in the undo log: {}",
+            e
+        );
+    }
+}
+
+// A line of a batch file, the global options come from the batch
+// invocation itself
+#[derive(Parser, Debug)]
+#[command(no_binary_name = true)]
+struct BatchLine {
+    #[command(subcommand)]
+    command: SubCommand,
+}
+
+// Replaces `$1` with the id created by the most recent line that created
+// something, `$2` with the one before it and so on, `$$` is a plain `$`
+fn substitute_ids(word: &str, created: &[String]) -> Result<String> {
+    let mut result = String::with_capacity(word.len());
+    let mut rest = word;
+
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            result.push('$');
+            rest = after;
+            continue;
+        }
+
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits == 0 {
+            result.push('$');
+            continue;
+        }
+
+        let n = rest[..digits].parse::<usize>().unwrap_or(usize::MAX);
+        let id = n
+            .checked_sub(1)
+            .and_then(|back| created.iter().rev().nth(back))
+            .ok_or_else(|| {
+                Error::Usage(format!(
+                    "${} doesn't refer to a created id",
+                    &rest[..digits]
+                ))
+            })?;
+        result.push_str(id);
+        rest = &rest[digits..];
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn parse_batch_line(line: &str, created: &[String]) -> Result<SubCommand> {
+    let words = shlex::split(line).ok_or_else(|| Error::Usage("unbalanced quotes".to_string()))?;
+    let words = words
+        .iter()
+        .map(|word| substitute_ids(word, created))
+        .collect::<Result<Vec<_>>>()?;
+    let line = BatchLine::try_parse_from(words).map_err(|e| {
+        let message = e.to_string();
+        let message = message.lines().next().unwrap_or_default();
+        Error::Usage(message.trim_start_matches("error: ").to_string())
+    })?;
+
+    // These are handled before there is a client, `execute` never sees them
+    match line.command {
+        SubCommand::Batch { .. } => {
+            return Err(Error::Usage(
+                "batch files can't run other batch files".to_string(),
+            ))
+        }
+        SubCommand::Completions { .. } | SubCommand::Config { .. } => {
+            return Err(Error::Usage(
+                "completions and config can't be used in batch files".to_string(),
+            ))
+        }
+        _ => {}
+    }
+
+    Ok(line.command)
+}
+
+// Every line shares the client and output of the batch invocation
+async fn run_batch(args: &Args, file: &Path, api: &Api, opts: &mut output::Options) -> Result<()> {
+    let content = std::fs::read_to_string(file)?;
+    let lines = content
+        .lines()
+        .enumerate()
+        .map(|(n, line)| (n + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .collect::<Vec<_>>();
+
+    let mut ran = 0;
+    let mut failed = 0;
+    let mut created = Vec::new();
+    for (n, line) in &lines {
+        ran += 1;
+        let result = match parse_batch_line(line, &created) {
+            Ok(command) => {
+                let args = Args {
+                    command,
+                    ..args.clone()
+                };
+                execute(args, api, opts).await
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(Some(id)) => created.push(id),
+            Ok(None) => {}
+            Err(e) => {
+                failed += 1;
+                eprintln!("error: line {}: {}", n, e);
+                if !args.continue_on_error {
+                    break;
+                }
+            }
+        }
+    }
+
+    if !args.quiet {
+        eprintln!("Ran {} of {} commands, {} failed", ran, lines.len(), failed);
+    }
+
+    if failed > 0 {
+        return Err(Error::Partial {
+            failed,
+            total: lines.len(),
+        });
+    }
+
+    Ok(())
+}
+
+async fn run(args: Args) -> Result<()> {
+    match &args.command {
+        SubCommand::Completions { shell } => {
+            let mut command = Args::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(*shell, &mut command, name, &mut std::io::stdout());
+            return Ok(());
+        }
+
+        SubCommand::Config {
+            command: ConfigCommand::Path,
+        } => {
+            let path = config::path()
+                .ok_or_else(|| Error::Usage("could not find a home directory".to_string()))?;
+            println!("{}", path.display());
+            return Ok(());
+        }
+
+        _ => {}
+    }
+
+    let config = config::load()?;
+    let profile = config.profile(args.profile.as_deref())?;
+    let server = args
+        .server
+        .clone()
+        .or(profile.server)
+        .or(config.server)
+        .unwrap_or_else(|| DEFAULT_SERVER.to_string());
+    let base_path = args
+        .base_path
+        .clone()
+        .or(profile.base_path)
+        .or(config.base_path)
+        .unwrap_or_else(|| DEFAULT_BASE_PATH.to_string());
+    let token = args.token.clone().or(profile.token).or(config.token);
+    let timeout = args.timeout.or(config.timeout).unwrap_or(DEFAULT_TIMEOUT);
+    let connect_timeout = args.connect_timeout.or(config.connect_timeout);
+    let out: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(std::io::stdout()),
+    };
+    let width = match terminal_size::terminal_size() {
+        _ if args.no_wrap => None,
+        Some((terminal_size::Width(width), _)) if args.out.is_none() => Some(width),
+        _ => Some(DEFAULT_TABLE_WIDTH),
+    };
+    let env_output = output::from_env().map_err(Error::Usage)?;
+    let env_compact = args.output.is_none() && env_output.is_some_and(|(_, compact)| compact);
+    let mut opts = output::Options {
+        output: args
+            .output
+            .or(env_output.map(|(output, _)| output))
+            .or(config.default_output)
+            .unwrap_or_default(),
+        color: args.out.is_none() && args.color.enabled(),
+        relative: args.relative,
+        compact: args.compact || env_compact,
+        format: args.format.clone(),
+        fields: args.fields.clone(),
+        width,
+        delimiter: if args.null_delimited {
+            "\0".to_string()
+        } else {
+            args.delimiter.clone().unwrap_or_else(|| "\t".to_string())
+        },
+        terminator: if args.null_delimited { "\0" } else { "\n" },
+        out,
+    };
+
+    let retry = Retry {
+        retries: args.retries,
+        base_delay: Duration::from_millis(args.retry_base_delay),
+        jitter: !args.no_jitter,
+    };
+    let mut headers = HeaderMap::new();
+    if let Some(token) = &token {
+        let mut value = HeaderValue::from_str(&format!("Bearer {}", token))
+            .map_err(|_| Error::Usage("token contains invalid characters".to_string()))?;
+        value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, value);
+    }
+
+    let mut builder = reqwest::Client::builder()
+        .timeout(Duration::from_secs(timeout))
+        .default_headers(headers);
+    if let Some(connect_timeout) = connect_timeout {
+        builder = builder.connect_timeout(Duration::from_secs(connect_timeout));
+    }
+    if let Some(proxy) = &args.proxy {
+        let proxy = reqwest::Proxy::all(proxy)
+            .map_err(|e| Error::Usage(format!("invalid proxy '{}': {}", proxy, e)))?;
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = &args.ca_cert {
+        let pem = std::fs::read(path)?;
+        let cert = reqwest::Certificate::from_pem(&pem)
+            .map_err(|e| Error::Usage(format!("invalid certificate {}: {}", path.display(), e)))?;
+        builder = builder.add_root_certificate(cert);
+    }
+    if args.insecure {
+        eprintln!(
+            "warning: TLS certificate verification is disabled, the connection is not secure"
+        );
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    let client = builder.build().map_err(Error::Client)?;
+    let quiet = args.quiet || args.dry_run;
+    // Progress bars would draw right over the tui
+    let interactive = matches!(args.command, SubCommand::Tui);
+    let progress = !quiet && !interactive && std::io::stdout().is_terminal();
+    let api = Api {
+        retry,
+        dry_run: args.dry_run,
+        progress,
+        idempotency_keys: !args.no_idempotency_keys,
+        retry_creates: args.retry_creates,
+        raw: args.raw,
+        concurrency: args.concurrency,
+        offline: args.offline,
+        max_age: args.max_age.map(Duration::from_secs),
+        slow_threshold: (args.verbose > 0).then(|| Duration::from_millis(args.slow_threshold_ms)),
+        ..Api::new(&server, &base_path, client)
+    };
+    let result = match &args.command {
+        SubCommand::Batch { file } => run_batch(&args, file, &api, &mut opts).await,
+        _ => execute(args.clone(), &api, &mut opts).await.map(|_| ()),
+    };
+    opts.out.flush()?;
+
+    if args.verbose > 0 && api.metrics.requests.load(Ordering::Relaxed) > 1 {
+        eprintln!("{}", api.metrics.summary());
+    }
+
+    result
+}
+
+// Runs a single command, returns the id of what it created (if anything)
+// so batch files can refer to it
+async fn execute(mut args: Args, api: &Api, opts: &mut output::Options) -> Result<Option<String>> {
+    let quiet = args.quiet || args.dry_run;
+    let yes = args.yes || args.dry_run;
+    let check_name = |name: &str| {
+        if !args.allow_empty && name.trim().is_empty() {
+            return Err(Error::Usage(
+                "name can't be empty, pass --allow-empty to use it anyway".to_string(),
+            ));
+        }
+        Ok(())
+    };
+
+    context::resolve(&mut args.command)?;
+    if args.prefix {
+        prefix::resolve(api, &mut args.command).await?;
+    }
+
+    let mut new_id = None;
+    match args.command {
+        SubCommand::GetAllProjects {
+            names_only,
+            limit,
+            offset,
+            since,
+            stream: true,
+            ..
+        } => {
+            if opts.format.is_none() && matches!(opts.output, Output::Table | Output::Count) {
+                return Err(Error::Usage(
+                    "--stream doesn't work with table or count output".to_string(),
+                ));
+            }
+
+            let (tx, mut rx) = mpsc::channel::<Project>(64);
+            let print = async {
+                let limit = limit.unwrap_or(usize::MAX);
+                let mut index = 0;
+                while let Some(project) = rx.recv().await {
+                    if since.is_some_and(|since| project.updated_at < since) {
+                        continue;
+                    }
+
+                    warn_malformed_color(args.verbose, &project.id, &project.color);
+                    // Keep draining after the limit so the parser can finish
+                    if index >= offset && index - offset < limit {
+                        output::print_streamed_project(
+                            opts,
+                            &project,
+                            index == offset,
+                            names_only,
+                        )?;
+                    }
+                    index += 1;
+                }
+                Ok::<_, Error>(())
+            };
+
+            let (parsed, printed) = tokio::join!(stream_all_projects(api, tx), print);
+            printed?;
+            parsed?;
+        }
+
+        SubCommand::GetAllProjects {
+            names_only,
+            limit,
+            offset,
+            since,
+            with_item_counts,
+            sort,
+            ..
+        } => {
+            let mut projects = get_all_projects(api).await?;
+            if let Some(since) = since {
+                projects.retain(|project| project.updated_at >= since);
+            }
+            for project in &projects {
+                warn_malformed_color(args.verbose, &project.id, &project.color);
+            }
+            sort::sort_projects(&mut projects, &sort);
+
+            let projects = projects
+                .into_iter()
+                .skip(offset)
+                .take(limit.unwrap_or(usize::MAX))
+                .collect::<Vec<_>>();
+            if names_only {
+                output::print_project_names(opts, &projects)?;
+            } else {
+                let item_counts = match with_item_counts {
+                    true => Some(count_items(api, &projects).await?),
+                    false => None,
+                };
+                output::print_projects(opts, &projects, item_counts.as_deref())?;
+            }
+        }
+
+        SubCommand::GetProject {
+            project_id,
+            items_only,
+            filter,
+            sort,
+        } => {
+            let mut project = get_cached_full_project(api, &project_id).await?;
+            warn_malformed_color(args.verbose, &project.id, &project.color);
+            for list in &mut project.lists {
+                filter::filter_items(&mut list.items, &filter);
+                sort::sort_items(&mut list.items, &sort);
+            }
+
+            if items_only {
+                let items = project.items().collect::<Vec<_>>();
+                output::print_items(opts, &items)?;
+            } else {
+                output::print_full_project(opts, &project)?;
+            }
+        }
+
+        SubCommand::GetList {
+            list_id,
+            filter,
+            sort,
+        } => {
+            let mut list = get_project_list(api, &list_id).await?;
+            filter::filter_items(&mut list.items, &filter);
+            sort::sort_items(&mut list.items, &sort);
+            output::print_list(opts, &list)?;
+        }
+
+        SubCommand::NewProject { name, color } => {
+            let id = new_project(api, &name, &color).await?;
+            if !quiet {
+                writeln!(opts.out, "{}", id)?;
+            }
+            new_id = Some(id);
+        }
+
+        SubCommand::RenameProject { project_id, name } => {
+            rename_project(api, &project_id, &name).await?;
+        }
+
+        SubCommand::SetProjectColor { project_id, color } => {
+            set_project_color(api, &project_id, &color).await?;
+            if args.verbose > 0 {
+                writeln!(opts.out, "Set color of project {} to {}", project_id, color)?;
+            }
+        }
+
+        SubCommand::DeleteProject { project_id } => {
+            let project = get_project(api, &project_id).await?;
+            if !yes {
+                let lists = project.lists.as_ref().map_or(0, |lists| lists.len());
+                confirm(&format!(
+                    "Delete project '{}' with {} lists?",
+                    project.name, lists
+                ))?;
+            }
+
+            delete_project(api, &project_id).await?;
+            log_deletion(
+                api,
+                undo::Entry::new("project", &project.id, &project.name, None),
+            );
+
+            if args.verbose > 0 {
+                writeln!(opts.out, "Deleted project {}", project_id)?;
+            }
+        }
+
+        SubCommand::GetItem { list_id, item_id } => {
+            let item = match find_item(api, &item_id, Some(&list_id)).await {
+                Ok(item) => Some(item),
+                Err(Error::NotFound(_)) => None,
+                Err(e) => return Err(e),
+            };
+
+            match item {
+                Some(item) if item.list_id == list_id => output::print_item(opts, &item)?,
+                _ => {
+                    return Err(Error::NotFound(format!(
+                        "item {} in list {}",
+                        item_id, list_id
+                    )))
+                }
+            }
+        }
+
+        SubCommand::UpdateItem {
+            item_id,
+            done_value,
+            name,
+            done,
+        } => {
+            let mut data = serde_json::Map::new();
+            if let Some(name) = name {
+                check_name(&name)?;
+                data.insert("name".to_string(), json!(name));
+            }
+            if let Some(done) = done.or(done_value) {
+                data.insert("done".to_string(), json!(done));
+            }
+            if data.is_empty() {
+                return Err(Error::Usage(
+                    "nothing to update, pass --name and/or --done".to_string(),
+                ));
+            }
+
+            update_item(api, &item_id, data.into()).await?;
+        }
+
+        SubCommand::ToggleItem { item_id } => {
+            let item = find_item(api, &item_id, None).await?;
+            update_item(api, &item_id, json!({ "done": !item.done })).await?;
+        }
+
+        SubCommand::RenameItem { item_id, name } => {
+            check_name(&name)?;
+            update_item(api, &item_id, json!({ "name": name })).await?;
+        }
+
+        SubCommand::ReorderItem { item_id, position } => {
+            reorder_item(api, &item_id, position).await?;
+        }
+
+        SubCommand::MoveItem {
+            item_id,
+            target_list_id,
+        } => {
+            if !list_exists(api, &target_list_id).await? {
+                return Err(Error::Usage(format!(
+                    "target list '{}' does not exist",
+                    target_list_id
+                )));
+            }
+
+            update_item(api, &item_id, json!({ "listId": target_list_id })).await?;
+
+            if args.verbose > 0 {
+                writeln!(
+                    opts.out,
+                    "Moved item {} to list {}",
+                    item_id, target_list_id
+                )?;
+            }
+        }
+
+        SubCommand::NewList {
+            project_id,
+            name,
+            full,
+        } => {
+            let name = name.expect("context::resolve fills in the name");
+            check_name(&name)?;
+            let id = new_list(api, &project_id, &name).await?;
+            if full && !quiet {
+                output::print_list(opts, &get_project_list(api, &id).await?)?;
+            } else if !quiet {
+                writeln!(opts.out, "{}", id)?;
+            }
+            new_id = Some(id);
+        }
+
+        SubCommand::NewLists { project_id, file } => {
+            let content = std::fs::read_to_string(&file)?;
+            let names = if content.trim_start().starts_with('[') {
+                serde_json::from_str::<Vec<String>>(&content)
+                    .map_err(|e| Error::ListFile(file, e))?
+            } else {
+                content.lines().map(str::to_string).collect()
+            };
+            let names = names
+                .iter()
+                .map(|name| name.trim())
+                .filter(|name| !name.is_empty())
+                .collect::<Vec<_>>();
+
+            let mut failed = Vec::new();
+            let mut created = 0;
+            for name in &names {
+                match new_list(api, &project_id, name).await {
+                    Ok(id) => {
+                        created += 1;
+                        if !quiet {
+                            writeln!(opts.out, "{}", id)?;
+                        }
+                        new_id = Some(id);
+                    }
+                    Err(e) => {
+                        failed.push((name, e));
+                        if !args.continue_on_error {
+                            break;
+                        }
+                    }
+                }
+            }
+
+            // stdout only gets the ids so they can be piped somewhere
+            if !quiet {
+                eprintln!("Created {} of {} lists", created, names.len());
+            }
+
+            for (name, e) in &failed {
+                eprintln!("error: failed to create list '{}': {}", name, e);
+            }
+
+            if !failed.is_empty() {
+                return Err(Error::Partial {
+                    failed: failed.len(),
+                    total: names.len(),
+                });
+            }
+        }
+
+        SubCommand::NewListItem {
+            list_id,
+            name,
+            by_name,
+            full,
+        } => {
+            let list_id = match by_name {
+                Some(project_id) => list_id_by_name(api, &project_id, &list_id).await?,
+                None => list_id,
+            };
+            if let Some(name) = name {
+                check_name(&name)?;
+                let id = new_list_item(api, &list_id, &name).await?;
+                if full && !quiet {
+                    output::print_item(opts, &find_item(api, &id, Some(&list_id)).await?)?;
+                } else if !quiet {
+                    writeln!(opts.out, "{}", id)?;
+                }
+                new_id = Some(id);
+            } else {
+                let mut ids = Vec::new();
+                for line in std::io::stdin().lines() {
+                    let line = line?;
+                    let name = line.trim();
+                    if name.is_empty() {
+                        continue;
+                    }
+
+                    let id = new_list_item(api, &list_id, name).await?;
+                    if !full && !quiet {
+                        writeln!(opts.out, "{}", id)?;
+                    }
+                    ids.push(id.clone());
+                    new_id = Some(id);
+                }
+
+                // One fetch of the list covers all of the new items
+                if full && !quiet {
+                    let list = get_project_list(api, &list_id).await?;
+                    let items = list
+                        .items()
+                        .filter(|i| ids.contains(&i.item.id))
+                        .collect::<Vec<_>>();
+                    output::print_items(opts, &items)?;
+                }
+            }
+        }
+
+        SubCommand::CopyList {
+            list_id,
+            name,
+            target_project,
+            keep_done,
+        } => {
+            if let Some(name) = &name {
+                check_name(name)?;
+            }
+            let list = get_project_list(api, &list_id).await?;
+            let project_id = target_project.as_deref().unwrap_or(&list.project_id);
+            let name = name.as_deref().unwrap_or(&list.name);
+
+            let id = new_list(api, project_id, name).await?;
+            for item in &list.items {
+                let item_id = new_list_item(api, &id, &item.name).await?;
+                if keep_done && item.done {
+                    update_item(api, &item_id, json!({ "done": true })).await?;
+                }
+            }
+
+            if !quiet {
+                writeln!(opts.out, "{}", id)?;
+            }
+            new_id = Some(id);
+        }
+
+        SubCommand::MoveList {
+            list_id,
+            target_project_id,
+        } => {
+            if !project_exists(api, &target_project_id).await? {
+                return Err(Error::Usage(format!(
+                    "target project '{}' does not exist",
+                    target_project_id
+                )));
+            }
+
+            update_list(api, &list_id, json!({ "projectId": target_project_id })).await?;
+
+            if !quiet {
+                writeln!(
+                    opts.out,
+                    "Moved list {} to project {}",
+                    list_id, target_project_id
+                )?;
+            }
+        }
+
+        SubCommand::RenameList { list_id, name } => {
+            check_name(&name)?;
+            rename_list(api, &list_id, &name).await?;
+        }
+
+        SubCommand::DeleteList { list_id } => {
+            let list = get_project_list(api, &list_id).await?;
+            if !yes {
+                confirm(&format!(
+                    "Delete list '{}' with {} items?",
+                    list.name,
+                    list.items.len()
+                ))?;
+            }
+
+            delete_list(api, &list_id).await?;
+
+            let mut entry = undo::Entry::new("list", &list.id, &list.name, Some(&list.project_id));
+            entry.items = list.items.into_iter().map(|i| i.name).collect();
+            log_deletion(api, entry);
+        }
+
+        SubCommand::DeleteListItem { item_id } => {
+            let item = find_item(api, &item_id, None).await?;
+            if !yes {
+                confirm(&format!("Delete item '{}'?", item.name))?;
+            }
+
+            delete_list_item(api, &item_id).await?;
+            log_deletion(
+                api,
+                undo::Entry::new("item", &item.id, &item.name, Some(&item.list_id)),
+            );
+        }
+
+        SubCommand::UndoLog { limit } => {
+            let entries = undo::load(limit)?;
+            output::print_undo_log(opts, &entries)?;
+        }
+
+        SubCommand::ClearCompleted { list_id } => {
+            let list = get_project_list(api, &list_id).await?;
+            let done = list.items.iter().filter(|i| i.done).collect::<Vec<_>>();
+            if done.is_empty() {
+                return Ok(None);
+            }
+
+            let prompt = format!(
+                "Delete {} completed items from list '{}'?",
+                done.len(),
+                list.name
+            );
+            if !yes {
+                confirm(&prompt)?;
+            }
+
+            let bar = api.progress_bar(done.len());
+            let ctrl_c = tokio::signal::ctrl_c();
+            tokio::pin!(ctrl_c);
+
+            let mut failed = Vec::new();
+            let mut deleted = 0;
+            let mut interrupted = false;
+            for item in &done {
+                tokio::select! {
+                    res = delete_list_item(api, &item.id) => match res {
+                        Ok(()) => {
+                            deleted += 1;
+                            let entry = undo::Entry::new("item", &item.id, &item.name, Some(&list.id));
+                            log_deletion(api, entry);
+                        }
+                        Err(e) if args.continue_on_error => failed.push((&item.id, e)),
+                        Err(e) => {
+                            failed.push((&item.id, e));
+                            break;
+                        }
+                    },
+                    _ = &mut ctrl_c => {
+                        interrupted = true;
+                        break;
+                    }
+                }
+                bar.inc(1);
+            }
+            bar.finish_and_clear();
+
+            if !quiet {
+                writeln!(opts.out, "Deleted {} items", deleted)?;
+            }
+
+            for (id, e) in &failed {
+                eprintln!("error: failed to delete item {}: {}", id, e);
+            }
+
+            if interrupted {
+                return Err(Error::Interrupted);
+            }
+
+            if !failed.is_empty() {
+                return Err(Error::Partial {
+                    failed: failed.len(),
+                    total: done.len(),
+                });
+            }
+        }
+
+        SubCommand::PruneEmptyLists { project_id } => {
+            let project = get_full_project(api, &project_id).await?;
+            let empty = project
+                .lists
+                .iter()
+                .filter(|list| list.items.is_empty())
+                .collect::<Vec<_>>();
+            if empty.is_empty() {
+                return Ok(None);
+            }
+
+            if args.dry_run {
+                for list in &empty {
+                    writeln!(opts.out, "Would delete list {} ({})", list.id, list.name)?;
+                }
+                return Ok(None);
+            }
+
+            if !yes {
+                confirm(&format!(
+                    "Delete {} empty lists from project '{}'?",
+                    empty.len(),
+                    project.name
+                ))?;
+            }
+
+            let mut failed = Vec::new();
+            let mut deleted = 0;
+            for list in &empty {
+                match delete_list(api, &list.id).await {
+                    Ok(()) => {
+                        deleted += 1;
+                        let entry =
+                            undo::Entry::new("list", &list.id, &list.name, Some(&project.id));
+                        log_deletion(api, entry);
+                    }
+                    Err(e) => {
+                        failed.push((&list.id, e));
+                        if !args.continue_on_error {
+                            break;
+                        }
+                    }
+                }
+            }
+
+            if !quiet {
+                writeln!(opts.out, "Deleted {} empty lists", deleted)?;
+            }
+
+            for (id, e) in &failed {
+                eprintln!("error: failed to delete list {}: {}", id, e);
+            }
+
+            if !failed.is_empty() {
+                return Err(Error::Partial {
+                    failed: failed.len(),
+                    total: empty.len(),
+                });
+            }
+        }
+
+        SubCommand::SearchItems {
+            project_id,
+            query,
+            all_projects,
+            filter,
+            sort,
+        } => {
+            let (projects, query) = match (project_id, query, all_projects) {
+                (Some(query), None, true) => (get_all_full_projects(api).await?, query),
+                (Some(project_id), Some(query), false) => (
+                    vec![get_cached_full_project(api, &project_id).await?],
+                    query,
+                ),
+                (_, _, true) => {
+                    return Err(Error::Usage(
+                        "--all-projects takes only the query, not a project id".to_string(),
+                    ))
+                }
+                (_, _, false) => {
+                    return Err(Error::Usage(
+                        "missing the query, pass a project id and a query".to_string(),
+                    ))
+                }
+            };
+
+            let query = query.to_lowercase();
+            let mut items = projects
+                .iter()
+                .flat_map(|project| project.items())
+                .filter(|i| i.item.name.to_lowercase().contains(&query))
+                .filter(|i| filter.matches(i.item))
+                .collect::<Vec<_>>();
+            items.sort_by(|a, b| sort::compare_items(a.item, b.item, &sort));
+            if all_projects {
+                output::print_items_across_projects(opts, &items)?;
+            } else {
+                output::print_items(opts, &items)?;
+            }
+        }
+
+        SubCommand::Stats { project_id } => {
+            let project = get_cached_full_project(api, &project_id).await?;
+            let stats = project
+                .lists
+                .iter()
+                .map(|list| {
+                    let done = list.items.iter().filter(|i| i.done).count();
+                    ListStats::new(&list.id, &list.name, done, list.items.len())
+                })
+                .collect::<Vec<_>>();
+            output::print_stats(opts, &stats)?;
+        }
+
+        SubCommand::Export { project_id } => {
+            let project = get_cached_full_project(api, &project_id).await?;
+            output::print_json(opts, &project)?;
+            writeln!(opts.out)?;
+        }
+
+        SubCommand::Diff { a, b } => {
+            let a = read_project_file(a)?;
+            let b = read_project_file(b)?;
+            output::print_diff(opts, &diff_projects(&a, &b))?;
+        }
+
+        SubCommand::Import { file } => {
+            let project = read_project_file(file)?;
+
+            let mut progress = ImportProgress::default();
+            let verbose = args.verbose > 0;
+            let continue_on_error = args.continue_on_error;
+            let result = tokio::select! {
+                res = import_project(api, &project, &mut progress, verbose, continue_on_error) => res,
+                _ = tokio::signal::ctrl_c() => Err(Error::Interrupted),
+            };
+
+            match result {
+                Ok(id) => {
+                    if !quiet {
+                        writeln!(opts.out, "{}", id)?;
+                    }
+                    new_id = Some(id);
+
+                    for (what, e) in &progress.failed {
+                        eprintln!("error: failed to import {}: {}", what, e);
+                    }
+
+                    if !progress.failed.is_empty() {
+                        let lists = project.lists.len();
+                        let items = project.lists.iter().map(|l| l.items.len()).sum::<usize>();
+                        return Err(Error::Partial {
+                            failed: progress.failed.len(),
+                            total: lists + items,
+                        });
+                    }
+                }
+
+                Err(e) => {
+                    if let Some(id) = &progress.project_id {
+                        let lists = project.lists.len();
+                        let items = project.lists.iter().map(|l| l.items.len()).sum::<usize>();
+                        let reason = match e {
+                            Error::Interrupted => "being interrupted",
+                            _ => "failing",
+                        };
+                        eprintln!(
+                            "Imported {} of {} lists and {} of {} items into project {} before {}",
+                            progress.lists, lists, progress.items, items, id, reason
+                        );
+                    }
+
+                    return Err(e);
+                }
+            }
+        }
+
+        SubCommand::Open {
+            project_id,
+            print_url,
+        } => {
+            let url = api.web_url(&format!("/project/{}", project_id));
+            if print_url {
+                writeln!(opts.out, "{}", url)?;
+            } else {
+                webbrowser::open(&url)?;
+            }
+        }
+
+        SubCommand::Version => {
+            let version = env!("CARGO_PKG_VERSION");
+            writeln!(opts.out, "onix {}", version)?;
+
+            match get_server_version(api).await? {
+                Some(server) => {
+                    match &server.build {
+                        Some(build) => writeln!(opts.out, "server {} ({})", server.version, build)?,
+                        None => writeln!(opts.out, "server {}", server.version)?,
+                    }
+
+                    if !compatible_versions(version, &server.version) {
+                        eprintln!(
+                            "warning: server {} might not be compatible with onix {}",
+                            server.version, version
+                        );
+                    }
+                }
+                None => writeln!(opts.out, "server unknown (no version endpoint)")?,
+            }
+        }
+
+        SubCommand::Exists { project, list } => {
+            let found = match (&project, &list) {
+                (Some(id), _) => project_exists(api, id).await?,
+                (_, Some(id)) => list_exists(api, id).await?,
+                (None, None) => unreachable!("clap requires one of them"),
+            };
+
+            if !found {
+                let what = match (project, list) {
+                    (Some(id), _) => format!("project {}", id),
+                    (_, id) => format!("list {}", id.unwrap_or_default()),
+                };
+                return Err(Error::Missing(what));
+            }
+        }
+
+        SubCommand::Init { project_id } => {
+            if !project_exists(api, &project_id).await? {
+                return Err(Error::NotFound(format!("project {}", project_id)));
+            }
+
+            let path = context::init(&project_id)?;
+            if !quiet {
+                writeln!(opts.out, "Wrote {}", path.display())?;
+            }
+        }
+
+        SubCommand::Ping => {
+            let elapsed = ping(api).await?;
+            if !quiet {
+                writeln!(opts.out, "{} is up ({} ms)", api.base, elapsed.as_millis())?;
+            }
+        }
+
+        SubCommand::Tui => {
+            if args.dry_run {
+                return Err(Error::Usage(
+                    "the tui doesn't support --dry-run".to_string(),
+                ));
+            }
+
+            tui::run(api).await?;
+        }
+
+        SubCommand::Watch {
+            project_id,
+            interval,
+        } => {
+            let interval = Duration::from_secs(interval);
+            tokio::select! {
+                res = watch_project(api, opts, &project_id, interval) => res?,
+                _ = tokio::signal::ctrl_c() => return Err(Error::Interrupted),
+            }
+        }
+
+        SubCommand::Batch { .. } | SubCommand::Completions { .. } | SubCommand::Config { .. } => {
+            unreachable!("run and parse_batch_line handle these before execute")
+        }
+    }
+
+    Ok(new_id)
+}
+
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(Targets::new().with_target(env!("CARGO_CRATE_NAME"), level))
+        .init();
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    init_logging(args.verbose);
+
+    match run(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        // Whatever got interrupted already said how far it got
+        Err(Error::Interrupted) => ExitCode::from(Error::Interrupted.exit_code()),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Response, TestServer};
+
+    fn api(server: &TestServer) -> Api {
+        Api::new(&server.url, "/api", reqwest::Client::new())
+    }
+
+    #[tokio::test]
+    async fn ids_are_encoded_in_the_query() {
+        let project = r##"{"id":"a&b","name":"Home","color":"#ff8800","createdAt":"2024-01-01T10:00:00.000Z","updatedAt":"2024-01-01T10:00:00.000Z"}"##;
+        let server =
+            TestServer::start(vec![Response::new(200, project), Response::new(200, "")]).await;
+        let api = api(&server);
+
+        get_project(&api, "a&b").await.unwrap();
+        delete_list_item(&api, "a&b").await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].target, "/api/project?id=a%26b");
+        assert_eq!(requests[1].target, "/api/project/list/item?id=a%26b");
+    }
+
+    #[tokio::test]
+    async fn list_bodies_use_the_field_names_of_the_server() {
+        let server =
+            TestServer::start(vec![Response::new(200, ""), Response::new(200, "l9")]).await;
+        let api = api(&server);
+
+        rename_list(&api, "l1", "Groceries").await.unwrap();
+        let id = new_list(&api, "p1", "Chores").await.unwrap();
+        assert_eq!(id, "l9");
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "PATCH");
+        assert_eq!(requests[0].target, "/api/project/list");
+        assert_eq!(
+            requests[0].json(),
+            json!({ "id": "l1", "data": { "name": "Groceries" } })
+        );
+
+        assert_eq!(requests[1].method, "POST");
+        assert_eq!(
+            requests[1].json(),
+            json!({ "name": "Chores", "projectId": "p1" })
+        );
+    }
+
+    #[tokio::test]
+    async fn reorder_patches_the_position() {
+        let item = r#"{"id":"i1","name":"Milk","done":false,"listId":"l1","position":2}"#;
+        let server =
+            TestServer::start(vec![Response::new(200, ""), Response::new(200, item)]).await;
+
+        reorder_item(&api(&server), "i1", 2).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "PATCH");
+        assert_eq!(requests[0].target, "/api/project/list/item");
+        assert_eq!(
+            requests[0].json(),
+            json!({ "id": "i1", "data": { "position": 2 } })
+        );
+    }
+
+    #[tokio::test]
+    async fn shape_errors_name_the_missing_field() {
+        let body = r#"{"id":"l1","name":"Groceries","projectId":"p1","items":[{"id":"i1","name":"Milk","listId":"l1"}]}"#;
+        let res = reqwest::Response::from(http::Response::new(body));
+        let api = Api::new("http://localhost", "", reqwest::Client::new());
+
+        match decode::<List>(&api, res).await {
+            Err(Error::Shape { path, .. }) => assert_eq!(path, "items[0].done"),
+            res => panic!("expected a shape error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn urls_for_servers_mounted_anywhere() {
+        for server in ["http://host", "http://host/"] {
+            for (base_path, url) in [
+                ("", "http://host/project/all"),
+                ("/", "http://host/project/all"),
+                ("onix/api", "http://host/onix/api/project/all"),
+                ("/onix/api/", "http://host/onix/api/project/all"),
+            ] {
+                let api = Api::new(server, base_path, reqwest::Client::new());
+                assert_eq!(api.url("/project/all"), url, "{} {:?}", server, base_path);
+            }
+
+            for (base_path, url) in [
+                ("", "http://host/project/p1"),
+                ("/api", "http://host/project/p1"),
+                ("/onix/api/", "http://host/onix/project/p1"),
+                ("/onix", "http://host/onix/project/p1"),
+            ] {
+                let api = Api::new(server, base_path, reqwest::Client::new());
+                assert_eq!(
+                    api.web_url("/project/p1"),
+                    url,
+                    "{} {:?}",
+                    server,
+                    base_path
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn project_files_name_the_missing_field() {
+        let file = std::env::temp_dir().join(format!("onix-test-{}.json", std::process::id()));
+        let content = json!({
+            "id": "p1",
+            "name": "Home",
+            "color": "#ff8800",
+            "createdAt": "2024-01-01T10:00:00.000Z",
+            "updatedAt": "2024-01-01T10:00:00.000Z",
+        });
+        std::fs::write(&file, content.to_string()).unwrap();
+
+        let result = read_project_file(file.clone());
+        std::fs::remove_file(&file).unwrap();
+        match result {
+            Err(Error::ProjectFile { path, .. }) => assert_eq!(path, "lists"),
+            res => panic!("expected a project file error, got {:?}", res.map(|p| p.id)),
+        }
+    }
+
+    #[tokio::test]
+    async fn html_error_pages_end_up_as_text() {
+        let page = "<html><body><h1>Internal Server Error</h1></body></html>";
+        let server = TestServer::start(vec![
+            Response::new(500, page).header("Content-Type", "text/html")
+        ])
+        .await;
+
+        match get_all_projects(&api(&server)).await {
+            Err(Error::Server { status, body, .. }) => {
+                assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+                assert_eq!(body, "Internal Server Error");
+            }
+            res => panic!("expected a server error, got {:?}", res.map(|p| p.len())),
+        }
+    }
+
+    #[tokio::test]
+    async fn ping_falls_back_to_get_without_head() {
+        let server =
+            TestServer::start(vec![Response::new(405, ""), Response::new(200, "[]")]).await;
+
+        ping(&api(&server)).await.unwrap();
+
+        let methods = server
+            .requests()
+            .into_iter()
+            .map(|r| r.method)
+            .collect::<Vec<_>>();
+        assert_eq!(methods, ["HEAD", "GET"]);
+    }
 }
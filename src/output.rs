@@ -0,0 +1,912 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::io::{self, IsTerminal, Write};
+
+use clap::ValueEnum;
+use comfy_table::{presets, ContentArrangement, Table};
+use serde::{Deserialize, Serialize};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::undo::Entry as UndoEntry;
+use crate::{
+    timestamp, DiffEntry, DiffKind, FlatItem, FullProject, List, ListItem, ListStats, Project,
+};
+
+// How an item changed since the previous refresh in `watch`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Completed,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Output {
+    #[default]
+    Json,
+    Table,
+    Plain,
+    Markdown,
+    Csv,
+    Yaml,
+    Ndjson,
+    // Just the number of projects, items or entries that would be printed
+    Count,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+                !no_color && std::io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+// Reads ONIX_OUTPUT, it takes the same values as --output and "compact"
+// as a shorthand for compact json, which is what CI usually wants
+pub fn from_env() -> Result<Option<(Output, bool)>, String> {
+    let Ok(value) = std::env::var("ONIX_OUTPUT") else {
+        return Ok(None);
+    };
+
+    if value.eq_ignore_ascii_case("compact") {
+        return Ok(Some((Output::Json, true)));
+    }
+
+    Output::from_str(&value, true)
+        .map(|output| Some((output, false)))
+        .map_err(|_| format!("invalid ONIX_OUTPUT '{}'", value))
+}
+
+pub struct Options {
+    pub output: Output,
+    pub color: bool,
+    pub relative: bool,
+    pub compact: bool,
+    pub format: Option<String>,
+    pub fields: Option<Vec<String>>,
+    // Width tables are wrapped to, `None` lets long cells run past the
+    // edge of the terminal
+    pub width: Option<u16>,
+    // Between the fields and after every line of plain output
+    pub delimiter: String,
+    pub terminator: &'static str,
+    pub out: Box<dyn Write>,
+}
+
+pub fn parse_color(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+// Paints `text` with the project color as an ANSI truecolor escape, a
+// malformed color leaves the text as is
+fn paint(opts: &Options, text: &str, color: &str) -> String {
+    match parse_color(color) {
+        Some((r, g, b)) if opts.color => {
+            format!("\x1b[38;2;{};{};{}m{}\x1b[0m", r, g, b, text)
+        }
+        _ => text.to_string(),
+    }
+}
+
+fn format_time(opts: &Options, time: &DateTime<Utc>) -> String {
+    if opts.relative {
+        timestamp::relative(time)
+    } else {
+        time.to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|' | '~'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+fn checkbox(done: bool) -> &'static str {
+    if done {
+        "- [x]"
+    } else {
+        "- [ ]"
+    }
+}
+
+// Replaces `{field}` placeholders in `template`, `{{` and `}}` produce
+// literal braces and placeholders for unknown fields are left untouched
+fn render_template(template: &str, field: impl Fn(&str) -> Option<String>) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find(['{', '}']) {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            result.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+
+        let placeholder = rest.strip_prefix('{').and_then(|r| r.split_once('}'));
+        match placeholder.and_then(|(name, after)| Some((field(name)?, after))) {
+            Some((value, after)) => {
+                result.push_str(&value);
+                rest = after;
+            }
+            None => {
+                result.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+fn print_plain(opts: &mut Options, fields: &[&dyn Display]) -> io::Result<()> {
+    let line = fields
+        .iter()
+        .map(|field| field.to_string())
+        .collect::<Vec<_>>()
+        .join(&opts.delimiter);
+    write!(opts.out, "{}{}", line, opts.terminator)
+}
+
+fn project_field(opts: &Options, project: &Project, name: &str) -> Option<String> {
+    let value = match name {
+        "id" => project.id.clone(),
+        "name" => project.name.clone(),
+        "color" => project.color.clone(),
+        "lists" => project.lists.as_ref().map_or(0, |l| l.len()).to_string(),
+        "created" => format_time(opts, &project.created_at),
+        "updated" => format_time(opts, &project.updated_at),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+fn item_field(item: &FlatItem, name: &str) -> Option<String> {
+    let value = match name {
+        "id" => item.item.id.clone(),
+        "name" => item.item.name.clone(),
+        "done" => item.item.done.to_string(),
+        "list" => item.list_name.to_string(),
+        "list_id" => item.item.list_id.clone(),
+        "project" => item.project_name.unwrap_or_default().to_string(),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+pub fn print_json<T: Serialize + ?Sized>(opts: &mut Options, value: &T) -> io::Result<()> {
+    if opts.compact {
+        serde_json::to_writer(&mut opts.out, value)?;
+    } else {
+        serde_json::to_writer_pretty(&mut opts.out, value)?;
+    }
+    Ok(())
+}
+
+fn print_yaml<T: Serialize + ?Sized>(opts: &mut Options, value: &T) -> io::Result<()> {
+    serde_yaml::to_writer(&mut opts.out, value).map_err(io::Error::other)
+}
+
+// Keeps only `fields` of every object, either the value itself or the
+// elements of an array. Asking for a field no object has is an error
+fn select_fields(value: serde_json::Value, fields: &[String]) -> io::Result<serde_json::Value> {
+    use serde_json::Value;
+
+    let was_array = value.is_array();
+    let mut objects = match value {
+        Value::Array(values) => values,
+        value => vec![value],
+    };
+
+    let mut valid = Vec::new();
+    for object in &objects {
+        if let Value::Object(map) = object {
+            for key in map.keys() {
+                if !valid.contains(key) {
+                    valid.push(key.clone());
+                }
+            }
+        }
+    }
+
+    // Nothing to check against when there are no objects at all
+    let unknown = fields.iter().find(|field| !valid.contains(field));
+    if let Some(field) = unknown.filter(|_| !objects.is_empty()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "unknown field '{}', valid fields are: {}",
+                field,
+                valid.join(", ")
+            ),
+        ));
+    }
+
+    for object in &mut objects {
+        if let Value::Object(map) = object {
+            *map = fields
+                .iter()
+                .filter_map(|field| Some((field.clone(), map.get(field)?.clone())))
+                .collect();
+        }
+    }
+
+    Ok(match objects.len() {
+        1 if !was_array => objects.remove(0),
+        _ => Value::Array(objects),
+    })
+}
+
+// Every element of an array gets its own line, flushed right away so that
+// whatever reads the other end of a pipe can start on it
+fn print_ndjson(opts: &mut Options, value: serde_json::Value) -> io::Result<()> {
+    let values = match value {
+        serde_json::Value::Array(values) => values,
+        value => vec![value],
+    };
+
+    for value in values {
+        serde_json::to_writer(&mut opts.out, &value)?;
+        writeln!(opts.out)?;
+        opts.out.flush()?;
+    }
+
+    Ok(())
+}
+
+// Json and yaml both print the structs as they are, only --fields and
+// ndjson need them as a json value first
+fn print_serialized<T: Serialize + ?Sized>(opts: &mut Options, value: &T) -> io::Result<()> {
+    if opts.fields.is_some() || opts.output == Output::Ndjson {
+        let mut value = serde_json::to_value(value)?;
+        if let Some(fields) = &opts.fields {
+            value = select_fields(value, fields)?;
+        }
+
+        return match opts.output {
+            Output::Yaml => print_yaml(opts, &value),
+            Output::Ndjson => print_ndjson(opts, value),
+            _ => print_json(opts, &value),
+        };
+    }
+
+    match opts.output {
+        Output::Yaml => print_yaml(opts, value),
+        _ => print_json(opts, value),
+    }
+}
+
+fn print_csv(opts: &mut Options, header: &[&str], rows: &[Vec<String>]) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(&mut opts.out);
+    writer.write_record(header)?;
+    for row in rows {
+        writer.write_record(row)?;
+    }
+    writer.flush()
+}
+
+fn print_table(opts: &mut Options, header: &[&str], rows: &[Vec<String>]) -> io::Result<()> {
+    let mut table = Table::new();
+    table.load_preset(presets::NOTHING).set_header(header);
+    match opts.width {
+        Some(width) => table
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_width(width),
+        None => table.set_content_arrangement(ContentArrangement::Disabled),
+    };
+
+    // Columns are separated by two spaces like before, the padding on
+    // the last column is trimmed again below
+    for column in table.column_iter_mut() {
+        column.set_padding((0, 2));
+    }
+
+    for row in rows {
+        table.add_row(row);
+    }
+
+    for line in table.lines() {
+        writeln!(opts.out, "{}", line.trim_end())?;
+    }
+
+    Ok(())
+}
+
+// `item_counts` adds an items column to table and csv output, one count
+// per project
+pub fn print_projects(
+    opts: &mut Options,
+    projects: &[Project],
+    item_counts: Option<&[usize]>,
+) -> io::Result<()> {
+    if let Some(template) = &opts.format {
+        let lines = projects
+            .iter()
+            .map(|project| render_template(template, |name| project_field(opts, project, name)))
+            .collect::<Vec<_>>();
+        for line in lines {
+            writeln!(opts.out, "{}", line)?;
+        }
+        return Ok(());
+    }
+
+    let rows = |name: &dyn Fn(&Project) -> String| {
+        projects
+            .iter()
+            .enumerate()
+            .map(|(index, project)| {
+                let lists = project.lists.as_ref().map_or(0, |lists| lists.len());
+                let mut row = vec![
+                    project.id.clone(),
+                    name(project),
+                    project.color.clone(),
+                    lists.to_string(),
+                ];
+                if let Some(counts) = item_counts {
+                    row.push(counts[index].to_string());
+                }
+                row
+            })
+            .collect::<Vec<_>>()
+    };
+
+    match opts.output {
+        Output::Json | Output::Yaml | Output::Ndjson => print_serialized(opts, projects),
+        Output::Count => writeln!(opts.out, "{}", projects.len()),
+
+        Output::Table => {
+            let mut rows = rows(&|project| paint(opts, &project.name, &project.color));
+            for (row, project) in rows.iter_mut().zip(projects) {
+                row.push(format_time(opts, &project.updated_at));
+            }
+            let header = match item_counts {
+                Some(_) => &["ID", "NAME", "COLOR", "LISTS", "ITEMS", "UPDATED"][..],
+                None => &["ID", "NAME", "COLOR", "LISTS", "UPDATED"][..],
+            };
+            print_table(opts, header, &rows)
+        }
+
+        Output::Csv => {
+            let rows = rows(&|project| project.name.clone());
+            let header = match item_counts {
+                Some(_) => &["id", "name", "color", "lists", "items"][..],
+                None => &["id", "name", "color", "lists"][..],
+            };
+            print_csv(opts, header, &rows)
+        }
+
+        Output::Plain => {
+            for project in projects {
+                let name = paint(opts, &project.name, &project.color);
+                print_plain(opts, &[&project.id, &name])?;
+            }
+            Ok(())
+        }
+
+        Output::Markdown => {
+            for project in projects {
+                let name = escape_markdown(&project.name);
+                writeln!(opts.out, "- {} (`{}`)", name, project.id)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ProjectName<'a> {
+    id: &'a str,
+    name: &'a str,
+}
+
+// Prints one project of `get-all-projects --stream` as soon as it has been
+// parsed, json and yaml can't be one big array anymore so every project
+// becomes its own line or document
+pub fn print_streamed_project(
+    opts: &mut Options,
+    project: &Project,
+    first: bool,
+    names_only: bool,
+) -> io::Result<()> {
+    let name = ProjectName {
+        id: &project.id,
+        name: &project.name,
+    };
+
+    match opts.output {
+        _ if opts.format.is_some() => print_projects(opts, std::slice::from_ref(project), None),
+
+        Output::Json | Output::Yaml | Output::Ndjson => {
+            let mut value = if names_only {
+                serde_json::to_value(&name)?
+            } else {
+                serde_json::to_value(project)?
+            };
+            if let Some(fields) = &opts.fields {
+                value = select_fields(value, fields)?;
+            }
+
+            if opts.output == Output::Yaml {
+                writeln!(opts.out, "---")?;
+                print_yaml(opts, &value)
+            } else {
+                serde_json::to_writer(&mut opts.out, &value)?;
+                writeln!(opts.out)
+            }
+        }
+
+        Output::Csv => {
+            let mut writer = csv::Writer::from_writer(&mut opts.out);
+            if names_only {
+                if first {
+                    writer.write_record(["id", "name"])?;
+                }
+                writer.write_record([&project.id, &project.name])?;
+            } else {
+                if first {
+                    writer.write_record(["id", "name", "color", "lists"])?;
+                }
+                let lists = project.lists.as_ref().map_or(0, |lists| lists.len());
+                writer.write_record([
+                    &project.id,
+                    &project.name,
+                    &project.color,
+                    &lists.to_string(),
+                ])?;
+            }
+            writer.flush()
+        }
+
+        // Tables need every row up front to size the columns
+        Output::Table => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "table output can't be streamed",
+        )),
+        Output::Count => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "count output can't be streamed",
+        )),
+
+        Output::Plain | Output::Markdown => {
+            print_projects(opts, std::slice::from_ref(project), None)
+        }
+    }
+}
+
+pub fn print_project_names(opts: &mut Options, projects: &[Project]) -> io::Result<()> {
+    if opts.format.is_some() {
+        return print_projects(opts, projects, None);
+    }
+
+    let rows = projects
+        .iter()
+        .map(|project| vec![project.id.clone(), project.name.clone()])
+        .collect::<Vec<_>>();
+
+    match opts.output {
+        Output::Json | Output::Yaml | Output::Ndjson => {
+            let names = projects
+                .iter()
+                .map(|project| ProjectName {
+                    id: &project.id,
+                    name: &project.name,
+                })
+                .collect::<Vec<_>>();
+            print_serialized(opts, &names)
+        }
+
+        Output::Table => print_table(opts, &["ID", "NAME"], &rows),
+        Output::Count => writeln!(opts.out, "{}", projects.len()),
+        Output::Csv => print_csv(opts, &["id", "name"], &rows),
+        Output::Plain | Output::Markdown => print_projects(opts, projects, None),
+    }
+}
+
+pub fn print_items(opts: &mut Options, items: &[FlatItem]) -> io::Result<()> {
+    print_flat_items(opts, items, false)
+}
+
+// Like print_items but with the project of every item up front, for items
+// gathered from more than one project
+pub fn print_items_across_projects(opts: &mut Options, items: &[FlatItem]) -> io::Result<()> {
+    print_flat_items(opts, items, true)
+}
+
+fn print_flat_items(opts: &mut Options, items: &[FlatItem], with_project: bool) -> io::Result<()> {
+    if let Some(template) = &opts.format {
+        let lines = items
+            .iter()
+            .map(|item| render_template(template, |name| item_field(item, name)))
+            .collect::<Vec<_>>();
+        for line in lines {
+            writeln!(opts.out, "{}", line)?;
+        }
+        return Ok(());
+    }
+
+    match opts.output {
+        Output::Json | Output::Yaml | Output::Ndjson => print_serialized(opts, items),
+        Output::Count => writeln!(opts.out, "{}", items.len()),
+
+        Output::Table => {
+            let rows = items
+                .iter()
+                .map(|i| {
+                    let mut row = vec![
+                        i.list_name.to_string(),
+                        i.item.id.clone(),
+                        i.item.name.clone(),
+                        i.item.done.to_string(),
+                    ];
+                    if with_project {
+                        row.insert(0, i.project_name.unwrap_or_default().to_string());
+                    }
+                    row
+                })
+                .collect::<Vec<_>>();
+            let header = ["PROJECT", "LIST", "ID", "NAME", "DONE"];
+            let header = if with_project {
+                &header[..]
+            } else {
+                &header[1..]
+            };
+            print_table(opts, header, &rows)
+        }
+
+        Output::Csv => {
+            let rows = items
+                .iter()
+                .map(|i| {
+                    vec![
+                        i.project_name.unwrap_or_default().to_string(),
+                        i.list_name.to_string(),
+                        i.item.id.clone(),
+                        i.item.name.clone(),
+                        i.item.done.to_string(),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            let header = ["project", "list", "item_id", "item_name", "done"];
+            print_csv(opts, &header, &rows)
+        }
+
+        Output::Plain => {
+            for i in items {
+                let fields: [&dyn Display; 3] = [&i.item.id, &i.item.done, &i.item.name];
+                if with_project {
+                    let project = i.project_name.unwrap_or_default();
+                    print_plain(opts, &[&project, fields[0], fields[1], fields[2]])?;
+                } else {
+                    print_plain(opts, &fields)?;
+                }
+            }
+            Ok(())
+        }
+
+        Output::Markdown => {
+            for i in items {
+                let mut name = escape_markdown(&i.item.name);
+                if with_project {
+                    let project = escape_markdown(i.project_name.unwrap_or_default());
+                    name = format!("{}: {}", project, name);
+                }
+                writeln!(opts.out, "{} {}", checkbox(i.item.done), name)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+pub fn print_item(opts: &mut Options, item: &ListItem) -> io::Result<()> {
+    if let Some(template) = &opts.format {
+        let flat = FlatItem {
+            project_name: None,
+            list_name: "",
+            item,
+        };
+        let line = render_template(template, |name| item_field(&flat, name));
+        return writeln!(opts.out, "{}", line);
+    }
+
+    let row = vec![item.id.clone(), item.name.clone(), item.done.to_string()];
+    match opts.output {
+        Output::Json | Output::Yaml | Output::Ndjson => print_serialized(opts, item),
+        Output::Count => writeln!(opts.out, "1"),
+        Output::Table => print_table(opts, &["ID", "NAME", "DONE"], &[row]),
+        Output::Csv => print_csv(opts, &["item_id", "item_name", "done"], &[row]),
+        Output::Plain => print_plain(opts, &[&item.id, &item.done, &item.name]),
+        Output::Markdown => {
+            let name = escape_markdown(&item.name);
+            writeln!(opts.out, "{} {}", checkbox(item.done), name)
+        }
+    }
+}
+
+fn print_markdown_list(opts: &mut Options, list: &List) -> io::Result<()> {
+    writeln!(opts.out, "## {}", escape_markdown(&list.name))?;
+    if !list.items.is_empty() {
+        writeln!(opts.out)?;
+    }
+
+    for item in &list.items {
+        let name = escape_markdown(&item.name);
+        writeln!(opts.out, "{} {}", checkbox(item.done), name)?;
+    }
+
+    Ok(())
+}
+
+pub fn print_list(opts: &mut Options, list: &List) -> io::Result<()> {
+    if opts.format.is_some() {
+        return print_items(opts, &list.items().collect::<Vec<_>>());
+    }
+
+    match opts.output {
+        Output::Json | Output::Yaml => print_serialized(opts, list),
+        Output::Ndjson => print_items(opts, &list.items().collect::<Vec<_>>()),
+        Output::Markdown => print_markdown_list(opts, list),
+        _ => print_items(opts, &list.items().collect::<Vec<_>>()),
+    }
+}
+
+pub fn print_full_project(opts: &mut Options, project: &FullProject) -> io::Result<()> {
+    if opts.format.is_some() {
+        return print_items(opts, &project.items().collect::<Vec<_>>());
+    }
+
+    match opts.output {
+        Output::Json | Output::Yaml => print_serialized(opts, project),
+        Output::Ndjson => print_items(opts, &project.items().collect::<Vec<_>>()),
+
+        Output::Markdown => {
+            writeln!(opts.out, "# {}", escape_markdown(&project.name))?;
+            for list in &project.lists {
+                writeln!(opts.out)?;
+                print_markdown_list(opts, list)?;
+            }
+            Ok(())
+        }
+
+        _ => print_items(opts, &project.items().collect::<Vec<_>>()),
+    }
+}
+
+pub fn print_stats(opts: &mut Options, stats: &[ListStats]) -> io::Result<()> {
+    let done = stats.iter().map(|s| s.done).sum();
+    let total = stats.iter().map(|s| s.total).sum();
+    let overall = ListStats::new("", "TOTAL", done, total);
+
+    match opts.output {
+        Output::Json | Output::Yaml | Output::Ndjson => print_serialized(opts, stats),
+        // The items still left to do, like the todos in a list
+        Output::Count => writeln!(opts.out, "{}", total - done),
+
+        Output::Table => {
+            let rows = stats
+                .iter()
+                .chain(std::iter::once(&overall))
+                .map(|s| {
+                    vec![
+                        s.name.clone(),
+                        s.done.to_string(),
+                        s.total.to_string(),
+                        format!("{}%", s.percent),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            print_table(opts, &["LIST", "DONE", "TOTAL", "PERCENT"], &rows)
+        }
+
+        Output::Csv => {
+            let rows = stats
+                .iter()
+                .map(|s| {
+                    vec![
+                        s.list_id.clone(),
+                        s.name.clone(),
+                        s.done.to_string(),
+                        s.total.to_string(),
+                        s.percent.to_string(),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            let header = ["list_id", "name", "done", "total", "percent"];
+            print_csv(opts, &header, &rows)
+        }
+
+        Output::Plain => {
+            for s in stats.iter().chain(std::iter::once(&overall)) {
+                let progress = format!("{}/{}", s.done, s.total);
+                let percent = format!("{}%", s.percent);
+                print_plain(opts, &[&s.list_id, &progress, &percent, &s.name])?;
+            }
+            Ok(())
+        }
+
+        Output::Markdown => {
+            for s in stats.iter().chain(std::iter::once(&overall)) {
+                writeln!(
+                    opts.out,
+                    "- {}: {}/{} ({}%)",
+                    escape_markdown(&s.name),
+                    s.done,
+                    s.total,
+                    s.percent
+                )?;
+            }
+            Ok(())
+        }
+    }
+}
+
+// Renders one refresh of `watch`, changed items are painted when color is
+// enabled and marked with a suffix otherwise
+pub fn print_watch(
+    opts: &mut Options,
+    project: &FullProject,
+    changes: &HashMap<&str, Change>,
+) -> io::Result<()> {
+    writeln!(opts.out, "{}", paint(opts, &project.name, &project.color))?;
+
+    for list in &project.lists {
+        writeln!(opts.out)?;
+        writeln!(opts.out, "{}", list.name)?;
+
+        for item in &list.items {
+            let name = match changes.get(item.id.as_str()) {
+                Some(Change::Added) if opts.color => paint(opts, &item.name, "#5fd75f"),
+                Some(Change::Completed) if opts.color => paint(opts, &item.name, "#d7af5f"),
+                Some(Change::Added) => format!("{} (new)", item.name),
+                Some(Change::Completed) => format!("{} (completed)", item.name),
+                None => item.name.clone(),
+            };
+            writeln!(opts.out, "  {} {}", checkbox(item.done), name)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn diff_details(entry: &DiffEntry) -> String {
+    let mut details = Vec::new();
+    if let Some(old_name) = entry.old_name {
+        details.push(format!("renamed from '{}'", old_name));
+    }
+    if let Some(done) = entry.done {
+        details.push(if done { "completed" } else { "reopened" }.to_string());
+    }
+    if let (Some(old), Some(new)) = (entry.old_list_id, entry.list_id) {
+        details.push(format!("moved from list {} to {}", old, new));
+    }
+
+    details.join(", ")
+}
+
+pub fn print_diff(opts: &mut Options, entries: &[DiffEntry]) -> io::Result<()> {
+    let change = |entry: &DiffEntry| match entry.change {
+        DiffKind::Added => "added",
+        DiffKind::Removed => "removed",
+        DiffKind::Changed => "changed",
+    };
+    let rows = entries
+        .iter()
+        .map(|e| {
+            vec![
+                change(e).to_string(),
+                e.kind.to_string(),
+                e.id.to_string(),
+                e.name.to_string(),
+                diff_details(e),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    match opts.output {
+        Output::Json | Output::Yaml | Output::Ndjson => print_serialized(opts, entries),
+        Output::Count => writeln!(opts.out, "{}", entries.len()),
+        Output::Table => print_table(opts, &["CHANGE", "TYPE", "ID", "NAME", "DETAILS"], &rows),
+        Output::Csv => print_csv(opts, &["change", "type", "id", "name", "details"], &rows),
+
+        Output::Plain | Output::Markdown => {
+            for (entry, row) in entries.iter().zip(&rows) {
+                let (sign, color) = match entry.change {
+                    DiffKind::Added => ("+", "#5fd75f"),
+                    DiffKind::Removed => ("-", "#d75f5f"),
+                    DiffKind::Changed => ("~", "#d7af5f"),
+                };
+
+                let mut line = format!("{} {} {} {}", sign, entry.kind, entry.id, entry.name);
+                if !row[4].is_empty() {
+                    line = format!("{} ({})", line, row[4]);
+                }
+                writeln!(opts.out, "{}", paint(opts, &line, color))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+pub fn print_undo_log(opts: &mut Options, entries: &[UndoEntry]) -> io::Result<()> {
+    let rows = entries
+        .iter()
+        .map(|e| {
+            vec![
+                format_time(opts, &e.deleted_at),
+                e.kind.clone(),
+                e.id.clone(),
+                e.name.clone(),
+                e.parent.clone().unwrap_or_default(),
+                e.command.clone(),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    match opts.output {
+        Output::Json | Output::Yaml | Output::Ndjson => print_serialized(opts, entries),
+        Output::Count => writeln!(opts.out, "{}", entries.len()),
+        Output::Table => print_table(
+            opts,
+            &["DELETED", "TYPE", "ID", "NAME", "PARENT", "COMMAND"],
+            &rows,
+        ),
+        Output::Csv => print_csv(
+            opts,
+            &["deleted_at", "type", "id", "name", "parent", "command"],
+            &rows,
+        ),
+        Output::Plain => {
+            for row in &rows {
+                let fields = row.iter().map(|f| f as &dyn Display).collect::<Vec<_>>();
+                print_plain(opts, &fields)?;
+            }
+            Ok(())
+        }
+        Output::Markdown => {
+            for (entry, row) in entries.iter().zip(&rows) {
+                let name = escape_markdown(&entry.name);
+                writeln!(
+                    opts.out,
+                    "- {} {} {} (`{}`)",
+                    row[0], entry.kind, name, entry.id
+                )?;
+            }
+            Ok(())
+        }
+    }
+}
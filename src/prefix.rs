@@ -0,0 +1,134 @@
+use crate::error::{Error, Result};
+use crate::{get_all_lists, get_all_projects, Api, Project, SubCommand};
+
+// Picks the one id starting with `prefix`, an exact match always wins so a
+// full id keeps working even when it's also the prefix of another one
+fn unique<'a>(kind: &str, prefix: &str, ids: impl Iterator<Item = &'a str>) -> Result<String> {
+    let mut matches = ids.filter(|id| id.starts_with(prefix)).collect::<Vec<_>>();
+    if matches.contains(&prefix) {
+        return Ok(prefix.to_string());
+    }
+
+    matches.sort_unstable();
+    matches.dedup();
+    match matches.as_slice() {
+        [] => Err(Error::NotFound(format!("{} {}", kind, prefix))),
+        [id] => Ok(id.to_string()),
+        ids => Err(Error::Usage(format!(
+            "'{}' matches several {}s: {}",
+            prefix,
+            kind,
+            ids.join(", ")
+        ))),
+    }
+}
+
+fn list_ids(projects: &[Project]) -> impl Iterator<Item = &str> {
+    projects
+        .iter()
+        .flat_map(|project| project.lists.as_deref().unwrap_or_default())
+        .map(|list| list.id.as_str())
+}
+
+async fn project(api: &Api, prefix: &str) -> Result<String> {
+    let projects = get_all_projects(api).await?;
+    unique("project", prefix, projects.iter().map(|p| p.id.as_str()))
+}
+
+async fn list(api: &Api, prefix: &str) -> Result<String> {
+    let projects = get_all_projects(api).await?;
+    unique("list", prefix, list_ids(&projects))
+}
+
+// There is no endpoint for all items, so this goes through every list of
+// every project
+async fn item(api: &Api, prefix: &str) -> Result<String> {
+    let lists = get_all_lists(api).await?;
+    let items = lists.iter().flat_map(|list| &list.items);
+    unique("item", prefix, items.map(|item| item.id.as_str()))
+}
+
+// Swaps every id of the command for the full id it is a prefix of, before
+// anything runs so the commands themselves only ever see full ids
+pub async fn resolve(api: &Api, command: &mut SubCommand) -> Result<()> {
+    match command {
+        SubCommand::GetProject { project_id, .. }
+        | SubCommand::RenameProject { project_id, .. }
+        | SubCommand::SetProjectColor { project_id, .. }
+        | SubCommand::DeleteProject { project_id }
+        | SubCommand::NewList { project_id, .. }
+        | SubCommand::NewLists { project_id, .. }
+        | SubCommand::PruneEmptyLists { project_id }
+        | SubCommand::Stats { project_id }
+        | SubCommand::Export { project_id }
+        | SubCommand::Open { project_id, .. }
+        | SubCommand::Watch { project_id, .. }
+        | SubCommand::Init { project_id } => {
+            *project_id = project(api, project_id).await?;
+        }
+
+        SubCommand::NewListItem {
+            by_name: Some(project_id),
+            ..
+        }
+        | SubCommand::SearchItems {
+            project_id: Some(project_id),
+            all_projects: false,
+            ..
+        } => {
+            *project_id = project(api, project_id).await?;
+        }
+
+        SubCommand::GetList { list_id, .. }
+        | SubCommand::NewListItem { list_id, .. }
+        | SubCommand::RenameList { list_id, .. }
+        | SubCommand::DeleteList { list_id }
+        | SubCommand::ClearCompleted { list_id } => {
+            *list_id = list(api, list_id).await?;
+        }
+
+        SubCommand::CopyList {
+            list_id,
+            target_project,
+            ..
+        } => {
+            *list_id = list(api, list_id).await?;
+            if let Some(target) = target_project {
+                *target = project(api, target).await?;
+            }
+        }
+
+        SubCommand::MoveList {
+            list_id,
+            target_project_id,
+        } => {
+            *list_id = list(api, list_id).await?;
+            *target_project_id = project(api, target_project_id).await?;
+        }
+
+        SubCommand::GetItem { list_id, item_id } => {
+            *list_id = list(api, list_id).await?;
+            *item_id = item(api, item_id).await?;
+        }
+
+        SubCommand::UpdateItem { item_id, .. }
+        | SubCommand::ToggleItem { item_id }
+        | SubCommand::RenameItem { item_id, .. }
+        | SubCommand::ReorderItem { item_id, .. }
+        | SubCommand::DeleteListItem { item_id } => {
+            *item_id = item(api, item_id).await?;
+        }
+
+        SubCommand::MoveItem {
+            item_id,
+            target_list_id,
+        } => {
+            *item_id = item(api, item_id).await?;
+            *target_list_id = list(api, target_list_id).await?;
+        }
+
+        _ => {}
+    }
+
+    Ok(())
+}
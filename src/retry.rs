@@ -0,0 +1,209 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::StatusCode;
+use tracing::debug;
+
+use crate::error::{Error, Result};
+use crate::Api;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Retry {
+    pub retries: u32,
+    pub base_delay: Duration,
+    // Off for runs that have to wait exactly the same every time
+    pub jitter: bool,
+}
+
+impl Retry {
+    // Exponential backoff with up to 50% random jitter on top, so that
+    // several clients failing at once don't all retry in lockstep
+    fn delay(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay.saturating_mul(2u32.saturating_pow(attempt));
+        if !self.jitter {
+            return delay;
+        }
+
+        let jitter = rand::thread_rng().gen_range(0.0..0.5);
+        delay.mul_f64(1.0 + jitter)
+    }
+}
+
+// Waiting longer than this for a rate limit is worse than just failing
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+// Connection problems, rate limits and a proxy that can't reach a
+// (restarting) server are worth another try, other errors won't go away
+fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::Http { source, .. } => source.is_connect() || source.is_timeout(),
+        Error::Server {
+            status,
+            retry_after,
+            ..
+        } => {
+            let retryable = matches!(
+                *status,
+                StatusCode::TOO_MANY_REQUESTS
+                    | StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+            );
+            retryable && retry_after.is_none_or(|after| after <= MAX_RETRY_AFTER)
+        }
+        _ => false,
+    }
+}
+
+// Retry-After is either a number of seconds or an http date like
+// "Wed, 21 Oct 2015 07:28:00 GMT"
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    // A date in the past means right away
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+// Header carrying a random key per created resource, a server that
+// supports it answers a repeated key with the original result
+pub const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+// Only use this for idempotent requests (GET/DELETE), a retried POST
+// might end up creating the same thing twice, see `with_idempotency_key`
+pub async fn with_retry(api: &Api, req: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+    let retry = api.retry;
+    let mut attempt = 0;
+    loop {
+        let req = req
+            .try_clone()
+            .expect("retried requests should not have a streaming body");
+
+        match crate::send(api, req).await {
+            Err(e) if attempt < retry.retries && is_transient(&e) => {
+                let delay = match e {
+                    Error::Server {
+                        retry_after: Some(after),
+                        ..
+                    } => after,
+                    _ => retry.delay(attempt),
+                };
+                debug!("retrying in {}ms after: {}", delay.as_millis(), e);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+
+            res => return res,
+        }
+    }
+}
+
+// A POST that timed out may still have created something, so they are
+// only retried with --retry-creates. Every retry reuses the same key so a
+// server that supports it can tell them apart from new requests
+pub async fn with_idempotency_key(
+    api: &Api,
+    req: reqwest::RequestBuilder,
+) -> Result<reqwest::Response> {
+    if !api.idempotency_keys {
+        return crate::send(api, req).await;
+    }
+
+    let key = format!("{:032x}", rand::random::<u128>());
+    let req = req.header(IDEMPOTENCY_KEY, key);
+    if api.retry_creates {
+        with_retry(api, req).await
+    } else {
+        crate::send(api, req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Response, TestServer};
+
+    fn server_error(status: u16, retry_after: Option<Duration>) -> Error {
+        Error::Server {
+            status: StatusCode::from_u16(status).unwrap(),
+            body: String::new(),
+            retry_after,
+        }
+    }
+
+    #[test]
+    fn retry_after_in_seconds() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 5 "), Some(Duration::from_secs(5)));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn retry_after_as_a_date() {
+        let date = (Utc::now() + chrono::Duration::seconds(30)).format("%a, %d %b %Y %H:%M:%S GMT");
+        let after = parse_retry_after(&date.to_string()).unwrap();
+        assert!(after > Duration::from_secs(25) && after <= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn retry_after_in_the_past_is_right_away() {
+        let after = parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT");
+        assert_eq!(after, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn transient_statuses() {
+        assert!(is_transient(&server_error(429, None)));
+        assert!(is_transient(&server_error(
+            503,
+            Some(Duration::from_secs(1))
+        )));
+        assert!(!is_transient(&server_error(500, None)));
+        assert!(!is_transient(&server_error(404, None)));
+        assert!(!is_transient(&Error::NotFound("/api/project".to_string())));
+    }
+
+    #[test]
+    fn long_retry_after_is_not_waited_for() {
+        let after = MAX_RETRY_AFTER + Duration::from_secs(1);
+        assert!(!is_transient(&server_error(429, Some(after))));
+        assert!(is_transient(&server_error(429, Some(MAX_RETRY_AFTER))));
+    }
+
+    #[tokio::test]
+    async fn rate_limited_requests_are_retried() {
+        let server = TestServer::start(vec![
+            Response::new(429, "slow down").header("Retry-After", "0"),
+            Response::new(200, "[]"),
+        ])
+        .await;
+        let mut api = Api::new(&server.url, "/api", reqwest::Client::new());
+        api.retry.retries = 1;
+
+        let url = api.url("/project/all");
+        let res = with_retry(&api, api.client.get(url)).await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "[]");
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_last_retry() {
+        let server = TestServer::start(vec![Response::new(503, ""), Response::new(503, "")]).await;
+        let mut api = Api::new(&server.url, "/api", reqwest::Client::new());
+        api.retry.retries = 1;
+
+        let url = api.url("/project/all");
+        let res = with_retry(&api, api.client.get(url)).await;
+        assert!(
+            matches!(res, Err(Error::Server { status, .. }) if status == StatusCode::SERVICE_UNAVAILABLE)
+        );
+        assert_eq!(server.requests().len(), 2);
+    }
+}
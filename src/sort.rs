@@ -0,0 +1,112 @@
+use std::cmp::Ordering;
+
+use clap::{Args, ValueEnum};
+
+use crate::{ListItem, Project};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProjectSort {
+    Name,
+    Created,
+    Updated,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItemSort {
+    Name,
+    Done,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct ProjectSortArgs {
+    #[arg(long, value_enum)]
+    pub sort: Option<ProjectSort>,
+
+    #[arg(long, requires = "sort")]
+    pub reverse: bool,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct ItemSortArgs {
+    #[arg(long, value_enum)]
+    pub sort: Option<ItemSort>,
+
+    #[arg(long, requires = "sort")]
+    pub reverse: bool,
+}
+
+fn compare_names(a: &str, b: &str) -> Ordering {
+    a.to_lowercase().cmp(&b.to_lowercase())
+}
+
+fn directed(ordering: Ordering, reverse: bool) -> Ordering {
+    if reverse {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+pub fn sort_projects(projects: &mut [Project], args: &ProjectSortArgs) {
+    let Some(sort) = args.sort else {
+        return;
+    };
+
+    projects.sort_by(|a, b| {
+        let ordering = match sort {
+            ProjectSort::Name => compare_names(&a.name, &b.name),
+            ProjectSort::Created => a.created_at.cmp(&b.created_at),
+            ProjectSort::Updated => a.updated_at.cmp(&b.updated_at),
+        };
+        directed(ordering, args.reverse)
+    });
+}
+
+pub fn compare_items(a: &ListItem, b: &ListItem, args: &ItemSortArgs) -> Ordering {
+    let ordering = match args.sort {
+        Some(ItemSort::Name) => compare_names(&a.name, &b.name),
+        Some(ItemSort::Done) => a.done.cmp(&b.done),
+        None => Ordering::Equal,
+    };
+    directed(ordering, args.reverse)
+}
+
+pub fn sort_items(items: &mut [ListItem], args: &ItemSortArgs) {
+    if args.sort.is_some() {
+        items.sort_by(|a, b| compare_items(a, b, args));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(id: &str, created_at: &str) -> Project {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": id,
+            "color": "#000000",
+            "createdAt": created_at,
+            "updatedAt": created_at,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn created_sorts_by_time_not_by_text() {
+        // 11:00 at +02:00 is 09:00 UTC, an hour before the other one even
+        // though it comes last as text
+        let mut projects = vec![
+            project("utc", "2024-01-01T10:00:00.000Z"),
+            project("offset", "2024-01-01T11:00:00.000+02:00"),
+        ];
+        let args = ProjectSortArgs {
+            sort: Some(ProjectSort::Created),
+            reverse: false,
+        };
+
+        sort_projects(&mut projects, &args);
+        let ids = projects.iter().map(|p| p.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, ["offset", "utc"]);
+    }
+}
@@ -0,0 +1,100 @@
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+// The server sends timestamps like `2023-06-12T09:41:27.000Z`, serialize
+// them back the same way so exported projects match what the server gave us
+pub fn serialize<S>(time: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&time.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let time = String::deserialize(deserializer)?;
+    DateTime::parse_from_rfc3339(&time)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(de::Error::custom)
+}
+
+pub fn relative(time: &DateTime<Utc>) -> String {
+    let seconds = (Utc::now() - *time).num_seconds();
+    if seconds < 0 {
+        return "in the future".to_string();
+    }
+
+    let (amount, unit) = match seconds {
+        0..=59 => return "just now".to_string(),
+        60..=3599 => (seconds / 60, "minute"),
+        3600..=86399 => (seconds / 3600, "hour"),
+        86400..=2591999 => (seconds / 86400, "day"),
+        2592000..=31535999 => (seconds / 2592000, "month"),
+        _ => (seconds / 31536000, "year"),
+    };
+
+    let plural = if amount == 1 { "" } else { "s" };
+    format!("{} {}{} ago", amount, unit, plural)
+}
+
+// Accepts either a duration counting back from now ("24h", "2weeks") or a
+// point in time, a plain date means midnight UTC
+pub fn parse_since(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(duration) = humantime::parse_duration(value) {
+        let duration = chrono::Duration::from_std(duration).map_err(|e| e.to_string())?;
+        return Ok(Utc::now() - duration);
+    }
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+        .map_err(|_| format!("'{}' is neither a duration like 24h nor a date", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Stamped {
+        #[serde(with = "super")]
+        time: DateTime<Utc>,
+    }
+
+    #[test]
+    fn server_timestamps_round_trip() {
+        let json = r#"{"time":"2023-06-12T09:41:27.000Z"}"#;
+        let stamped = serde_json::from_str::<Stamped>(json).unwrap();
+        assert_eq!(
+            stamped.time,
+            Utc.with_ymd_and_hms(2023, 6, 12, 9, 41, 27).unwrap()
+        );
+        assert_eq!(serde_json::to_string(&stamped).unwrap(), json);
+    }
+
+    #[test]
+    fn offsets_are_converted_to_utc() {
+        let json = r#"{"time":"2023-06-12T11:41:27.000+02:00"}"#;
+        let stamped = serde_json::from_str::<Stamped>(json).unwrap();
+        assert_eq!(
+            serde_json::to_string(&stamped).unwrap(),
+            r#"{"time":"2023-06-12T09:41:27.000Z"}"#
+        );
+    }
+
+    #[test]
+    fn rejects_timestamps_that_are_not_rfc3339() {
+        for time in ["2023-06-12 09:41:27", "12/06/2023", ""] {
+            let json = serde_json::json!({ "time": time });
+            assert!(serde_json::from_value::<Stamped>(json).is_err(), "{}", time);
+        }
+    }
+}
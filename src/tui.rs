@@ -0,0 +1,342 @@
+use std::io::{self, Stdout};
+
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use futures::StreamExt;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Borders, List as ListWidget, ListItem as Row, ListState, Paragraph};
+use ratatui::{Frame, Terminal};
+use serde_json::json;
+
+use crate::error::Result;
+use crate::{
+    delete_list, delete_list_item, get_all_projects, get_full_project, undo, update_item, Api,
+    FullProject, List, Project,
+};
+
+type Backend = CrosstermBackend<Stdout>;
+
+const HELP: &str = "arrows move, space toggles, d deletes, q quits";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Projects,
+    Lists,
+    Items,
+}
+
+struct App {
+    projects: Vec<Project>,
+    // The full data of the selected project, loaded when it's selected
+    project: Option<FullProject>,
+    focus: Pane,
+    projects_state: ListState,
+    lists_state: ListState,
+    items_state: ListState,
+    // Set by `d`, the next key answers the confirmation
+    confirm_delete: bool,
+    status: String,
+}
+
+// Puts the terminal back the way it was, also when the loop bails out
+// with an error
+struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+        let _ = crossterm::execute!(io::stdout(), LeaveAlternateScreen);
+    }
+}
+
+fn first(len: usize) -> Option<usize> {
+    (len > 0).then_some(0)
+}
+
+impl App {
+    fn list(&self) -> Option<&List> {
+        let project = self.project.as_ref()?;
+        project.lists.get(self.lists_state.selected()?)
+    }
+
+    fn len(&self, pane: Pane) -> usize {
+        match pane {
+            Pane::Projects => self.projects.len(),
+            Pane::Lists => self.project.as_ref().map_or(0, |p| p.lists.len()),
+            Pane::Items => self.list().map_or(0, |l| l.items.len()),
+        }
+    }
+
+    fn state(&mut self, pane: Pane) -> &mut ListState {
+        match pane {
+            Pane::Projects => &mut self.projects_state,
+            Pane::Lists => &mut self.lists_state,
+            Pane::Items => &mut self.items_state,
+        }
+    }
+
+    // Moves the selection of the focused pane, returns whether it changed
+    fn step(&mut self, delta: isize) -> bool {
+        let len = self.len(self.focus);
+        let state = self.state(self.focus);
+        let Some(current) = state.selected() else {
+            return false;
+        };
+
+        let next = current
+            .saturating_add_signed(delta)
+            .min(len.saturating_sub(1));
+        state.select(Some(next));
+        next != current
+    }
+
+    async fn load_project(&mut self, api: &Api) -> Result<()> {
+        let Some(index) = self.projects_state.selected() else {
+            return Ok(());
+        };
+
+        self.project = None;
+        let project = get_full_project(api, &self.projects[index].id).await?;
+        self.lists_state.select(first(project.lists.len()));
+        self.project = Some(project);
+        self.select_first_item();
+        Ok(())
+    }
+
+    fn select_first_item(&mut self) {
+        let len = self.len(Pane::Items);
+        self.items_state.select(first(len));
+    }
+
+    async fn toggle(&mut self, api: &Api) -> Result<()> {
+        let (Some(list), Some(index)) = (self.lists_state.selected(), self.items_state.selected())
+        else {
+            return Ok(());
+        };
+        let Some(project) = &mut self.project else {
+            return Ok(());
+        };
+
+        let item = &mut project.lists[list].items[index];
+        update_item(api, &item.id, json!({ "done": !item.done })).await?;
+        item.done = !item.done;
+        Ok(())
+    }
+
+    fn delete_prompt(&self) -> Option<String> {
+        match self.focus {
+            Pane::Projects => None,
+            Pane::Lists => self
+                .list()
+                .map(|list| format!("Delete list '{}'?", list.name)),
+            Pane::Items => {
+                let item = self.list()?.items.get(self.items_state.selected()?)?;
+                Some(format!("Delete item '{}'?", item.name))
+            }
+        }
+    }
+
+    async fn delete(&mut self, api: &Api) -> Result<()> {
+        let Some(list_index) = self.lists_state.selected() else {
+            return Ok(());
+        };
+        let Some(project) = &mut self.project else {
+            return Ok(());
+        };
+
+        let entry = match self.focus {
+            Pane::Projects => return Ok(()),
+
+            Pane::Lists => {
+                let list = &project.lists[list_index];
+                delete_list(api, &list.id).await?;
+
+                let list = project.lists.remove(list_index);
+                let mut entry = undo::Entry::new("list", &list.id, &list.name, Some(&project.id));
+                entry.items = list.items.into_iter().map(|i| i.name).collect();
+
+                let len = project.lists.len();
+                self.lists_state
+                    .select(first(len).map(|_| list_index.min(len - 1)));
+                self.select_first_item();
+                entry
+            }
+
+            Pane::Items => {
+                let Some(index) = self.items_state.selected() else {
+                    return Ok(());
+                };
+                let list = &mut project.lists[list_index];
+                delete_list_item(api, &list.items[index].id).await?;
+
+                let item = list.items.remove(index);
+                let len = list.items.len();
+                self.items_state
+                    .select(first(len).map(|_| index.min(len - 1)));
+                undo::Entry::new("item", &item.id, &item.name, Some(&item.list_id))
+            }
+        };
+
+        // Written straight to the status line, stderr is hidden behind the
+        // alternate screen
+        if let Err(e) = undo::record(&entry) {
+            self.status = format!("failed to record the deletion in the undo log: {}", e);
+        }
+        Ok(())
+    }
+
+    // Returns false once the user wants to quit
+    async fn handle(&mut self, api: &Api, key: KeyEvent) -> Result<bool> {
+        if self.confirm_delete {
+            self.confirm_delete = false;
+            self.status = HELP.to_string();
+            if matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+                self.delete(api).await?;
+            }
+            return Ok(true);
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(false)
+            }
+
+            KeyCode::Left => {
+                self.focus = match self.focus {
+                    Pane::Items => Pane::Lists,
+                    _ => Pane::Projects,
+                }
+            }
+            KeyCode::Right => {
+                self.focus = match self.focus {
+                    Pane::Projects => Pane::Lists,
+                    _ => Pane::Items,
+                }
+            }
+
+            KeyCode::Up | KeyCode::Down => {
+                let delta = if key.code == KeyCode::Up { -1 } else { 1 };
+                if self.step(delta) {
+                    match self.focus {
+                        Pane::Projects => self.load_project(api).await?,
+                        Pane::Lists => self.select_first_item(),
+                        Pane::Items => {}
+                    }
+                }
+            }
+
+            KeyCode::Char(' ') if self.focus == Pane::Items => self.toggle(api).await?,
+
+            KeyCode::Char('d') => match self.delete_prompt() {
+                Some(prompt) => {
+                    self.confirm_delete = true;
+                    self.status = format!("{} [y/N]", prompt);
+                }
+                None => self.status = "only lists and items can be deleted here".to_string(),
+            },
+
+            _ => {}
+        }
+
+        Ok(true)
+    }
+}
+
+fn pane<'a>(title: &'a str, rows: Vec<Row<'a>>, focused: bool) -> ListWidget<'a> {
+    let mut block = Block::default().borders(Borders::ALL).title(title);
+    if focused {
+        block = block.border_style(Style::default().add_modifier(Modifier::BOLD));
+    }
+
+    ListWidget::new(rows)
+        .block(block)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+}
+
+fn draw(frame: &mut Frame<Backend>, app: &mut App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(frame.size());
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(25),
+            Constraint::Percentage(25),
+            Constraint::Percentage(50),
+        ])
+        .split(rows[0]);
+
+    let projects = app
+        .projects
+        .iter()
+        .map(|p| Row::new(p.name.as_str()))
+        .collect();
+    let lists = app
+        .project
+        .iter()
+        .flat_map(|p| &p.lists)
+        .map(|l| Row::new(l.name.as_str()))
+        .collect();
+    let items = app
+        .list()
+        .into_iter()
+        .flat_map(|l| &l.items)
+        .map(|i| Row::new(format!("{} {}", if i.done { "[x]" } else { "[ ]" }, i.name)))
+        .collect();
+
+    let focus = app.focus;
+    let projects = pane("Projects", projects, focus == Pane::Projects);
+    let lists = pane("Lists", lists, focus == Pane::Lists);
+    let items = pane("Items", items, focus == Pane::Items);
+    frame.render_stateful_widget(projects, columns[0], &mut app.projects_state);
+    frame.render_stateful_widget(lists, columns[1], &mut app.lists_state);
+    frame.render_stateful_widget(items, columns[2], &mut app.items_state);
+    frame.render_widget(Paragraph::new(app.status.as_str()), rows[1]);
+}
+
+pub async fn run(api: &Api) -> Result<()> {
+    let projects = get_all_projects(api).await?;
+    let mut app = App {
+        projects_state: ListState::default().with_selected(first(projects.len())),
+        projects,
+        project: None,
+        focus: Pane::Projects,
+        lists_state: ListState::default(),
+        items_state: ListState::default(),
+        confirm_delete: false,
+        status: HELP.to_string(),
+    };
+    app.load_project(api).await?;
+
+    terminal::enable_raw_mode()?;
+    let _guard = Guard;
+    crossterm::execute!(io::stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+
+    let mut events = EventStream::new();
+    loop {
+        terminal.draw(|frame| draw(frame, &mut app))?;
+
+        let Some(event) = events.next().await else {
+            return Ok(());
+        };
+        let Event::Key(key) = event? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        // A failed request shouldn't end the session, it just shows up in
+        // the status line
+        match app.handle(api, key).await {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(e) => app.status = format!("error: {}", e),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::error::Result;
+use crate::timestamp;
+
+// One deleted project, list or item, with enough of it kept around to
+// recreate it by hand
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    #[serde(with = "timestamp")]
+    pub deleted_at: DateTime<Utc>,
+    pub command: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub id: String,
+    pub name: String,
+    // The project of a list or the list of an item
+    pub parent: Option<String>,
+    // Names of the items a deleted list still had
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub items: Vec<String>,
+}
+
+impl Entry {
+    pub fn new(kind: &str, id: &str, name: &str, parent: Option<&str>) -> Self {
+        Self {
+            deleted_at: Utc::now(),
+            command: command_line(),
+            kind: kind.to_string(),
+            id: id.to_string(),
+            name: name.to_string(),
+            parent: parent.map(str::to_string),
+            items: Vec::new(),
+        }
+    }
+}
+
+fn command_line() -> String {
+    let args = std::env::args().collect::<Vec<_>>();
+    shlex::try_join(args.iter().map(String::as_str)).unwrap_or_else(|_| args.join(" "))
+}
+
+fn path() -> Option<PathBuf> {
+    let dirs = ProjectDirs::from("", "", env!("CARGO_PKG_NAME"))?;
+    Some(dirs.config_dir().join("undo.log"))
+}
+
+// The log is one JSON object per line and only ever appended to
+pub fn record(entry: &Entry) -> Result<()> {
+    let Some(path) = path() else {
+        return Ok(());
+    };
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_string(entry).map_err(std::io::Error::from)?;
+    line.push('\n');
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+
+    Ok(())
+}
+
+// The `limit` most recent entries, oldest first. Lines that don't parse
+// are skipped so one bad write doesn't hide everything else
+pub fn load(limit: usize) -> Result<Vec<Entry>> {
+    let Some(path) = path() else {
+        return Ok(Vec::new());
+    };
+
+    let file = match std::fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        match serde_json::from_str::<Entry>(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => debug!("ignoring broken undo log line in {}: {}", path.display(), e),
+        }
+    }
+
+    let skip = entries.len().saturating_sub(limit);
+    Ok(entries.into_iter().skip(skip).collect())
+}